@@ -0,0 +1,52 @@
+use learn_game::board::BoardSpec;
+use learn_game::config::TrainingConfig;
+use learn_game::players::{ComputerPlayerRLmax, ComputerPlayerRLmin};
+use learn_game::q_table::QTable;
+use learn_game::Game;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+fn bench<F: FnMut()>(name: &str, iterations: u32, mut f: F) -> Duration {
+    for _ in 0..iterations / 10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let per_iter = start.elapsed() / iterations;
+    println!("{name:<40} {per_iter:>12?}/iter");
+    per_iter
+}
+
+// Times whole Q-learning episodes, each run starting from the same seed and an empty table.
+fn training_episode(spec: BoardSpec, bitboard: bool, episodes: u32) -> Duration {
+    let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+    let rl_min = Box::new(ComputerPlayerRLmin::new("RLmin".to_string()));
+    let mut game = Game::with_seed(rl_max, rl_min, spec, 7).with_config(TrainingConfig {
+        seed: Some(7),
+        bitboard,
+        ..TrainingConfig::default()
+    });
+    let mut q = QTable::new();
+    let name = if bitboard {
+        "bitboard:   training episode"
+    } else {
+        "char array: training episode"
+    };
+    bench(name, episodes, || {
+        black_box(game.learn_episode(Some(&mut q)));
+    })
+}
+
+fn main() {
+    for spec in [BoardSpec::square(3), BoardSpec::new(6, 7, 4), BoardSpec::new(8, 16, 5)] {
+        println!("{}x{} board, {} in a row", spec.rows, spec.cols, spec.win_len);
+        let char_episode = training_episode(spec, false, 20_000);
+        let bit_episode = training_episode(spec, true, 20_000);
+        println!(
+            "speed-up: training episode {:.1}x\n",
+            char_episode.as_secs_f64() / bit_episode.as_secs_f64()
+        );
+    }
+}
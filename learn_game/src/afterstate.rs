@@ -0,0 +1,237 @@
+use crate::board::{Board, BoardSpec, GameState, IsGameOver};
+use crate::config::{Hyperparameters, TrainingConfig};
+use crate::metrics::EpisodeStats;
+use crate::players::{Marks, Player};
+use crate::q_table::QTable;
+use crate::rng::{self, GameRng};
+use itertools::Itertools;
+use rand::prelude::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+
+// Values of the positions left behind by a move, from X's point of view: X maximizes, 0 minimizes.
+// Different move orders reaching the same position share one entry.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AfterstateTable {
+    values: BTreeMap<String, f32>,
+    #[serde(default)]
+    symmetric: bool,
+}
+
+#[derive(Debug)]
+pub struct AfterstatePlayer<V: ValueFunction = AfterstateTable> {
+    pub name: String,
+    pub mark: Marks,
+    pub params: Hyperparameters,
+    pub values: V,
+}
+
+impl Deref for AfterstateTable {
+    type Target = BTreeMap<String, f32>;
+    fn deref(&self) -> &Self::Target {
+        &self.values
+    }
+}
+
+impl DerefMut for AfterstateTable {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.values
+    }
+}
+
+// Anything that scores the position left behind by a move, from X's point of view: X maximizes, 0 minimizes.
+pub trait ValueFunction {
+    fn value(&self, state: &GameState) -> f32;
+    fn update_toward(&mut self, state: &GameState, expected: f32, params: &Hyperparameters) -> f32;
+
+    fn greedy_move(&self, state: &GameState, mark: Marks, rng: &mut GameRng) -> (usize, usize) {
+        let scored = state
+            .available_moves()
+            .into_iter()
+            .map(|mv| (mv, self.value(&afterstate(state, &mv, mark))));
+        let best = if mark == Marks::CROSS {
+            scored.max_set_by(|a, b| a.1.total_cmp(&b.1))
+        } else {
+            scored.min_set_by(|a, b| a.1.total_cmp(&b.1))
+        };
+        best.choose(rng).unwrap().0
+    }
+    fn choose_move(&self, state: &GameState, mark: Marks, epsilon: f32, rng: &mut GameRng) -> (usize, usize) {
+        if rng.gen_range(0_f32..=1_f32) < epsilon {
+            *state.available_moves().choose(rng).unwrap()
+        } else {
+            self.greedy_move(state, mark, rng)
+        }
+    }
+    // One self-play game: each afterstate is moved towards the one that follows it, whoever moved next.
+    fn learn_episode(&mut self, spec: BoardSpec, params: &Hyperparameters, rng: &mut GameRng) -> EpisodeStats {
+        let mut stats = EpisodeStats::default();
+        let mut state = GameState::with_spec(spec);
+        let mut mark = Marks::CROSS;
+        let mut previous: Option<GameState> = None;
+        loop {
+            let mv = self.choose_move(&state, mark, params.epsilon, rng);
+            state[[mv.0, mv.1]] = mark.as_char();
+            let is_over = state.is_game_over(&mark);
+            let target = match is_over {
+                IsGameOver::InPlay => self.value(&state),
+                IsGameOver::Win if mark == Marks::CROSS => 1.0,
+                IsGameOver::Win => -1.0,
+                IsGameOver::Drawn => 0.0,
+            };
+            if let Some(previous) = previous.take() {
+                stats.td_error += self.update_toward(&previous, params.gamma * target, params).abs();
+                stats.updates += 1;
+            }
+            if is_over == IsGameOver::InPlay {
+                previous = Some(state.clone());
+                mark = mark.other();
+                continue;
+            }
+            stats.td_error += self.update_toward(&state, target, params).abs();
+            stats.updates += 1;
+            if is_over == IsGameOver::Win {
+                stats.winner = Some(mark);
+            }
+            return stats;
+        }
+    }
+}
+
+pub fn afterstate(state: &GameState, mv: &(usize, usize), mark: Marks) -> GameState {
+    let mut after = state.clone();
+    after[[mv.0, mv.1]] = mark.as_char();
+    after
+}
+
+impl AfterstateTable {
+    pub fn new() -> Self {
+        AfterstateTable {
+            values: BTreeMap::new(),
+            symmetric: false,
+        }
+    }
+    pub fn with_symmetry() -> Self {
+        AfterstateTable {
+            values: BTreeMap::new(),
+            symmetric: true,
+        }
+    }
+    pub fn is_symmetric(&self) -> bool {
+        self.symmetric
+    }
+    pub fn key(&self, state: &GameState) -> String {
+        if self.symmetric {
+            state.canonicalize().0
+        } else {
+            state.to_state_key()
+        }
+    }
+}
+
+impl ValueFunction for AfterstateTable {
+    // Positions never seen are neutral.
+    fn value(&self, state: &GameState) -> f32 {
+        self.get(&self.key(state)).copied().unwrap_or(0.0)
+    }
+    fn update_toward(&mut self, state: &GameState, expected: f32, params: &Hyperparameters) -> f32 {
+        let key = self.key(state);
+        let value = self.entry(key).or_insert(0.0);
+        let td_error = expected - *value;
+        *value += params.alpha * td_error;
+        td_error
+    }
+}
+
+pub fn train(config: &TrainingConfig, spec: BoardSpec) -> AfterstateTable {
+    let mut table = AfterstateTable::with_symmetry();
+    learn(&mut table, config, spec);
+    table
+}
+
+pub fn learn<V: ValueFunction>(values: &mut V, config: &TrainingConfig, spec: BoardSpec) {
+    let mut rng = config.seed.map_or_else(rng::from_entropy, rng::seeded);
+    for episode in 0..config.num_episodes {
+        values.learn_episode(spec, &config.at(episode), &mut rng);
+    }
+}
+
+impl<V: ValueFunction> AfterstatePlayer<V> {
+    pub fn new(name: String, values: V) -> Self {
+        AfterstatePlayer {
+            name,
+            mark: Marks::None,
+            params: Hyperparameters::greedy(),
+            values,
+        }
+    }
+}
+
+impl<V: ValueFunction> Player for AfterstatePlayer<V> {
+    fn set_mark(&mut self, mark: Marks) {
+        self.mark = mark;
+    }
+    fn get_mark(&self) -> &Marks {
+        &self.mark
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn choose_move(&self, board: &Board, _q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.values.choose_move(&board.current_state, self.mark, self.params.epsilon, rng)
+    }
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.choose_move(board, q, rng)
+    }
+    fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
+        let (a, b) = *mv;
+        *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
+    }
+    fn set_hyperparameters(&mut self, params: &Hyperparameters) {
+        self.params = *params;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Schedule;
+
+    #[test]
+    fn is_afterstate_table_working() {
+        let table = AfterstateTable::with_symmetry();
+        let mut first = GameState::new();
+        first[[0, 0]] = 'X';
+        first[[1, 1]] = '0';
+        first[[0, 2]] = 'X';
+        let mut second = GameState::new();
+        second[[0, 2]] = 'X';
+        second[[1, 1]] = '0';
+        second[[2, 2]] = 'X';
+        // Reached by different move orders and related by a rotation.
+        assert_eq!(table.key(&first), table.key(&second));
+
+        let config = TrainingConfig {
+            num_episodes: 5_000,
+            seed: Some(3),
+            epsilon: Schedule::Constant { value: 0.3 },
+            alpha: Schedule::Constant { value: 0.2 },
+            ..TrainingConfig::default()
+        };
+        let mut player = AfterstatePlayer::new("Afterstate".to_string(), train(&config, BoardSpec::default()));
+        player.set_mark(Marks::CROSS);
+        let mut board = Board::new();
+        board.current_state[[0, 0]] = 'X';
+        board.current_state[[0, 1]] = 'X';
+        board.current_state[[1, 0]] = '0';
+        board.current_state[[1, 1]] = '0';
+        let mut q = QTable::new();
+        let mut rng = rng::seeded(1);
+        assert_eq!(player.choose_move(&board, &mut q, &mut rng), (0, 2));
+        board.current_state[[1, 0]] = '-';
+        player.set_mark(Marks::NOUGHT);
+        assert_eq!(player.choose_move(&board, &mut q, &mut rng), (0, 2));
+    }
+}
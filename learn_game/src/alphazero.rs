@@ -0,0 +1,473 @@
+use crate::board::{Board, BoardSpec, GameState, IsGameOver};
+use crate::dqn::one_hot;
+use crate::evaluate::{self, EvalOptions};
+use crate::mlp::Mlp;
+use crate::players::{Marks, Player};
+use crate::q_table::QTable;
+use crate::rng::{self, GameRng};
+use itertools::Itertools;
+use ndarray::{s, Array1, Array2};
+use rand::prelude::SliceRandom;
+use rand::Rng;
+
+// Prior probability of each legal move.
+pub type Priors = Vec<((usize, usize), f32)>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlphaZeroConfig {
+    pub hidden: usize,
+    pub simulations: usize,
+    pub c_puct: f32,
+    pub iterations: usize,
+    pub games_per_iteration: usize,
+    // Moves sampled in proportion to the visit counts before play turns greedy.
+    pub sampling_moves: usize,
+    // Share of random noise mixed into the root priors during self-play.
+    pub noise: f32,
+    // Most recent positions kept for training across iterations.
+    pub window: usize,
+    pub epochs: usize,
+    pub batch_size: usize,
+    pub learning_rate: f32,
+    pub gate_games: usize,
+    pub gate_threshold: f32,
+}
+
+// Policy logits for every square plus one value output, sharing the hidden layers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyValueNet {
+    pub spec: BoardSpec,
+    pub mlp: Mlp,
+}
+
+// A self-play position with the search's visit distribution and the final outcome for the side to move.
+#[derive(Debug, Clone)]
+pub struct Example {
+    pub input: Array1<f32>,
+    pub policy: Array1<f32>,
+    pub value: f32,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    state: GameState,
+    to_move: Marks,
+    mv: Option<(usize, usize)>,
+    children: Vec<usize>,
+    prior: f32,
+    visits: u32,
+    // From the point of view of the player who moved into the node.
+    value_sum: f32,
+    terminal: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generation {
+    pub iteration: usize,
+    pub examples: usize,
+    pub loss: f32,
+    pub score: f32,
+    pub accepted: bool,
+}
+
+#[derive(Debug)]
+pub struct AlphaZero {
+    pub spec: BoardSpec,
+    pub config: AlphaZeroConfig,
+    pub best: PolicyValueNet,
+    pub examples: Vec<Example>,
+    pub history: Vec<Generation>,
+    rng: GameRng,
+}
+
+#[derive(Debug)]
+pub struct AlphaZeroPlayer {
+    pub name: String,
+    pub mark: Marks,
+    pub net: PolicyValueNet,
+    pub simulations: usize,
+    pub c_puct: f32,
+    pub sampling_moves: usize,
+}
+
+impl Default for AlphaZeroConfig {
+    fn default() -> Self {
+        AlphaZeroConfig {
+            hidden: 64,
+            simulations: 50,
+            c_puct: 1.5,
+            iterations: 10,
+            games_per_iteration: 25,
+            sampling_moves: 3,
+            noise: 0.25,
+            window: 5_000,
+            epochs: 5,
+            batch_size: 32,
+            learning_rate: 0.02,
+            gate_games: 20,
+            gate_threshold: 0.55,
+        }
+    }
+}
+
+impl PolicyValueNet {
+    pub fn new(spec: BoardSpec, hidden: usize, rng: &mut GameRng) -> Self {
+        let cells = spec.cells();
+        PolicyValueNet {
+            spec,
+            mlp: Mlp::new(&[3 * cells, hidden, hidden, cells + 1], rng),
+        }
+    }
+    // Priors over the legal moves and the value for the side to move, in [-1, 1].
+    pub fn evaluate(&self, state: &GameState, to_move: Marks) -> (Priors, f32) {
+        let output = self.mlp.forward(&one_hot(state, to_move));
+        let cols = self.spec.cols;
+        let moves = state.available_moves();
+        let best = moves.iter().map(|mv| output[mv.0 * cols + mv.1]).fold(f32::NEG_INFINITY, f32::max);
+        let weights: Vec<f32> = moves.iter().map(|mv| (output[mv.0 * cols + mv.1] - best).exp()).collect();
+        let sum: f32 = weights.iter().sum();
+        let priors = moves.into_iter().zip(weights).map(|(mv, w)| (mv, w / sum)).collect();
+        (priors, output[self.spec.cells()].tanh())
+    }
+    // One SGD step on cross-entropy to the visit distribution plus squared error to the outcome.
+    pub fn train_batch(&mut self, batch: &[&Example], learning_rate: f32) -> f32 {
+        let cells = self.spec.cells();
+        let n = batch.len() as f32;
+        let mut inputs = Array2::zeros((batch.len(), 3 * cells));
+        for (row, example) in batch.iter().enumerate() {
+            inputs.row_mut(row).assign(&example.input);
+        }
+        let activations = self.mlp.activations(&inputs);
+        let output = activations.last().unwrap();
+        let mut delta = Array2::zeros(output.raw_dim());
+        let mut loss = 0.0;
+        for (row, example) in batch.iter().enumerate() {
+            let logits = output.slice(s![row, ..cells]);
+            let best = logits.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+            let exp = logits.mapv(|l| (l - best).exp());
+            let p = &exp / exp.sum();
+            loss -= example.policy.iter().zip(p.iter()).map(|(pi, p)| pi * p.max(1e-8).ln()).sum::<f32>();
+            delta.slice_mut(s![row, ..cells]).assign(&((&p - &example.policy) / n));
+            let v = output[[row, cells]].tanh();
+            loss += (v - example.value).powi(2);
+            delta[[row, cells]] = 2.0 * (v - example.value) * (1.0 - v * v) / n;
+        }
+        self.mlp.backward(&activations, delta, learning_rate);
+        loss / n
+    }
+}
+
+impl Node {
+    fn new(state: GameState, to_move: Marks, mv: Option<(usize, usize)>, prior: f32, terminal: Option<f32>) -> Self {
+        Node {
+            state,
+            to_move,
+            mv,
+            children: Vec::new(),
+            prior,
+            visits: 0,
+            value_sum: 0.0,
+            terminal,
+        }
+    }
+    fn q(&self) -> f32 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.value_sum / self.visits as f32
+        }
+    }
+}
+
+// Adds the children of a leaf with the network's priors and returns its value for the side to move.
+fn expand(nodes: &mut Vec<Node>, leaf: usize, net: &PolicyValueNet) -> f32 {
+    let (priors, value) = net.evaluate(&nodes[leaf].state, nodes[leaf].to_move);
+    let mover = nodes[leaf].to_move;
+    for (mv, prior) in priors {
+        let mut state = nodes[leaf].state.clone();
+        state[[mv.0, mv.1]] = mover.as_char();
+        let terminal = match state.is_game_over(&mover) {
+            IsGameOver::Win => Some(1.0),
+            IsGameOver::Drawn => Some(0.0),
+            IsGameOver::InPlay => None,
+        };
+        nodes.push(Node::new(state, mover.other(), Some(mv), prior, terminal));
+        let child = nodes.len() - 1;
+        nodes[leaf].children.push(child);
+    }
+    value
+}
+
+fn select(nodes: &[Node], parent: usize, c_puct: f32) -> usize {
+    let sqrt_visits = (nodes[parent].visits as f32 + 1.0).sqrt();
+    let score = |&child: &usize| {
+        let node = &nodes[child];
+        node.q() + c_puct * node.prior * sqrt_visits / (1.0 + node.visits as f32)
+    };
+    *nodes[parent].children.iter().max_by(|a, b| score(a).total_cmp(&score(b))).unwrap()
+}
+
+// PUCT search from `state`; returns the visit count of every legal move.
+pub fn search(
+    net: &PolicyValueNet,
+    state: &GameState,
+    to_move: Marks,
+    simulations: usize,
+    c_puct: f32,
+    noise: f32,
+    rng: &mut GameRng,
+) -> Vec<((usize, usize), u32)> {
+    let mut nodes = vec![Node::new(state.clone(), to_move, None, 1.0, None)];
+    expand(&mut nodes, 0, net);
+    if noise > 0.0 {
+        // Uniform noise stands in for the usual Dirichlet noise, which would need another dependency.
+        let eta: Vec<f32> = nodes[0].children.iter().map(|_| rng.gen::<f32>()).collect();
+        let sum: f32 = eta.iter().sum();
+        for (child, eta) in nodes[0].children.clone().into_iter().zip(eta) {
+            nodes[child].prior = (1.0 - noise) * nodes[child].prior + noise * eta / sum;
+        }
+    }
+    for _ in 0..simulations {
+        let mut path = vec![0];
+        let mut current = 0;
+        while !nodes[current].children.is_empty() {
+            current = select(&nodes, current, c_puct);
+            path.push(current);
+        }
+        // From the point of view of the player who moved into the leaf.
+        let mut value = match nodes[current].terminal {
+            Some(value) => value,
+            None => -expand(&mut nodes, current, net),
+        };
+        for &i in path.iter().rev() {
+            nodes[i].visits += 1;
+            nodes[i].value_sum += value;
+            value = -value;
+        }
+    }
+    nodes[0]
+        .children
+        .iter()
+        .map(|&child| (nodes[child].mv.unwrap(), nodes[child].visits))
+        .collect()
+}
+
+// Samples in proportion to the visits, or takes the most visited move with ties broken at random.
+pub fn choose_move(visits: &[((usize, usize), u32)], sample: bool, rng: &mut GameRng) -> (usize, usize) {
+    if sample {
+        if let Ok(choice) = visits.choose_weighted(rng, |(_, n)| *n) {
+            return choice.0;
+        }
+    }
+    visits.iter().max_set_by_key(|(_, n)| *n).choose(rng).unwrap().0
+}
+
+pub fn self_play(net: &PolicyValueNet, config: &AlphaZeroConfig, rng: &mut GameRng) -> Vec<Example> {
+    let spec = net.spec;
+    let mut state = GameState::with_spec(spec);
+    let mut mark = Marks::CROSS;
+    let mut positions = Vec::new();
+    loop {
+        let visits = search(net, &state, mark, config.simulations, config.c_puct, config.noise, rng);
+        let total = visits.iter().map(|(_, n)| n).sum::<u32>().max(1) as f32;
+        let mut policy = Array1::zeros(spec.cells());
+        for ((r, c), n) in &visits {
+            policy[r * spec.cols + c] = *n as f32 / total;
+        }
+        positions.push((one_hot(&state, mark), policy, mark));
+        let mv = choose_move(&visits, positions.len() <= config.sampling_moves, rng);
+        state[[mv.0, mv.1]] = mark.as_char();
+        match state.is_game_over(&mark) {
+            IsGameOver::InPlay => mark = mark.other(),
+            is_over => {
+                let winner = (is_over == IsGameOver::Win).then_some(mark);
+                return positions
+                    .into_iter()
+                    .map(|(input, policy, to_move)| Example {
+                        input,
+                        policy,
+                        value: match winner {
+                            Some(winner) if winner == to_move => 1.0,
+                            Some(_) => -1.0,
+                            None => 0.0,
+                        },
+                    })
+                    .collect();
+            }
+        }
+    }
+}
+
+impl AlphaZero {
+    pub fn new(spec: BoardSpec, config: AlphaZeroConfig, seed: Option<u64>) -> Self {
+        let mut rng = seed.map_or_else(rng::from_entropy, rng::seeded);
+        AlphaZero {
+            spec,
+            config,
+            best: PolicyValueNet::new(spec, config.hidden, &mut rng),
+            examples: Vec::new(),
+            history: Vec::new(),
+            rng,
+        }
+    }
+    pub fn player(&self, name: String) -> AlphaZeroPlayer {
+        AlphaZeroPlayer::new(name, self.best.clone(), &self.config)
+    }
+    // Self-play with the best network, train a copy on the recent positions, and keep it if it wins the gate.
+    pub fn iterate(&mut self) -> Generation {
+        for _ in 0..self.config.games_per_iteration {
+            let games = self_play(&self.best, &self.config, &mut self.rng);
+            self.examples.extend(games);
+        }
+        let excess = self.examples.len().saturating_sub(self.config.window);
+        self.examples.drain(..excess);
+
+        let mut candidate = self.best.clone();
+        let mut loss = 0.0;
+        for _ in 0..self.config.epochs {
+            let mut batch: Vec<&Example> = self.examples.iter().collect();
+            batch.shuffle(&mut self.rng);
+            let chunks = batch.chunks(self.config.batch_size);
+            let n = chunks.len() as f32;
+            loss = chunks.map(|chunk| candidate.train_batch(chunk, self.config.learning_rate)).sum::<f32>() / n;
+        }
+
+        // Sampling the opening moves keeps the gate games from all being the same two games.
+        let mut challenger = AlphaZeroPlayer::new("Candidate".to_string(), candidate.clone(), &self.config);
+        challenger.sampling_moves = self.config.sampling_moves;
+        let mut champion = self.player("Best".to_string());
+        champion.sampling_moves = self.config.sampling_moves;
+        let options = EvalOptions {
+            spec: self.spec,
+            seed: Some(self.rng.gen()),
+            ..EvalOptions::default()
+        };
+        let report = evaluate::evaluate(
+            Box::new(challenger),
+            &mut QTable::new(),
+            Box::new(champion),
+            &mut QTable::new(),
+            self.config.gate_games,
+            &options,
+        );
+        let score = report.win_rate.estimate + report.draw_rate.estimate / 2.0;
+        let accepted = score >= self.config.gate_threshold;
+        if accepted {
+            self.best = candidate;
+        }
+        let generation = Generation {
+            iteration: self.history.len(),
+            examples: self.examples.len(),
+            loss,
+            score,
+            accepted,
+        };
+        self.history.push(generation.clone());
+        generation
+    }
+    pub fn run(&mut self) {
+        for _ in 0..self.config.iterations {
+            self.iterate();
+        }
+    }
+}
+
+impl AlphaZeroPlayer {
+    pub fn new(name: String, net: PolicyValueNet, config: &AlphaZeroConfig) -> Self {
+        AlphaZeroPlayer {
+            name,
+            mark: Marks::None,
+            net,
+            simulations: config.simulations,
+            c_puct: config.c_puct,
+            sampling_moves: 0,
+        }
+    }
+}
+
+impl Player for AlphaZeroPlayer {
+    fn set_mark(&mut self, mark: Marks) {
+        self.mark = mark;
+    }
+    fn get_mark(&self) -> &Marks {
+        &self.mark
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn choose_move(&self, board: &Board, _q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        let state = &board.current_state;
+        let visits = search(&self.net, state, self.mark, self.simulations, self.c_puct, 0.0, rng);
+        let played = state.spec.cells() - state.available_moves().len();
+        choose_move(&visits, played < self.sampling_moves, rng)
+    }
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.choose_move(board, q, rng)
+    }
+    fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
+        let (a, b) = *mv;
+        *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_alphazero_search_working() {
+        let mut rng = rng::seeded(0);
+        let net = PolicyValueNet::new(BoardSpec::default(), 16, &mut rng);
+        let mut state = GameState::new();
+        state[[0, 0]] = 'X';
+        state[[0, 1]] = 'X';
+        state[[1, 1]] = '0';
+        // Even an untrained network finds the block once the search sees X win everywhere else.
+        let visits = search(&net, &state, Marks::NOUGHT, 400, 1.5, 0.0, &mut rng);
+        assert_eq!(visits.iter().map(|(_, n)| n).sum::<u32>(), 400);
+        assert_eq!(choose_move(&visits, false, &mut rng), (0, 2));
+        state[[2, 0]] = '0';
+        state[[1, 0]] = 'X';
+        state[[2, 1]] = 'X';
+        let visits = search(&net, &state, Marks::NOUGHT, 400, 1.5, 0.0, &mut rng);
+        assert_eq!(choose_move(&visits, false, &mut rng), (0, 2));
+        let (priors, value) = net.evaluate(&state, Marks::NOUGHT);
+        assert!((priors.iter().map(|(_, p)| p).sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(value.abs() <= 1.0);
+    }
+
+    #[test]
+    fn is_alphazero_working() {
+        let config = AlphaZeroConfig {
+            hidden: 16,
+            simulations: 16,
+            iterations: 2,
+            games_per_iteration: 4,
+            epochs: 2,
+            batch_size: 8,
+            gate_games: 4,
+            ..AlphaZeroConfig::default()
+        };
+        let mut rng = rng::seeded(1);
+        let spec = BoardSpec::new(4, 4, 3);
+        let net = PolicyValueNet::new(spec, config.hidden, &mut rng);
+        let examples = self_play(&net, &config, &mut rng);
+        assert!(examples.len() >= 5 && examples.len() <= spec.cells());
+        assert!(examples.iter().all(|e| (e.policy.sum() - 1.0).abs() < 1e-5));
+        // The last mover either won or filled the board.
+        let last = examples.last().unwrap().value;
+        assert!(last == 1.0 || last == 0.0);
+        assert!(examples.iter().rev().step_by(2).all(|e| e.value == last));
+        assert!(examples.iter().rev().skip(1).step_by(2).all(|e| e.value == -last));
+
+        let mut alphazero = AlphaZero::new(BoardSpec::default(), config, Some(2));
+        alphazero.run();
+        assert_eq!(alphazero.history.len(), 2);
+        assert!(alphazero.history.iter().all(|g| g.loss.is_finite() && (0.0..=1.0).contains(&g.score)));
+        let mut player = alphazero.player("AlphaZero".to_string());
+        player.set_mark(Marks::CROSS);
+        let board = Board::new();
+        let mv = player.choose_move(&board, &mut QTable::new(), &mut rng);
+        assert!(board.current_state.available_moves().contains(&mv));
+    }
+}
@@ -0,0 +1,264 @@
+use crate::board::{BoardSpec, GameState, IsGameOver};
+use crate::players::Marks;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+
+#[derive(Debug)]
+pub struct WinMasks {
+    pub all: Vec<u128>,
+    pub by_cell: Vec<Vec<u128>>,
+}
+
+// The representation searches play and undo moves on; a GameState converts to one at the root.
+#[derive(Debug, Clone)]
+pub struct BitBoard {
+    pub crosses: u128,
+    pub noughts: u128,
+    pub spec: BoardSpec,
+    masks: Arc<WinMasks>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BitMoves {
+    empty: u128,
+    cols: usize,
+}
+
+static WIN_MASKS: OnceLock<Mutex<HashMap<BoardSpec, Arc<WinMasks>>>> = OnceLock::new();
+
+impl WinMasks {
+    pub fn for_spec(spec: BoardSpec) -> Arc<WinMasks> {
+        let mut cache = WIN_MASKS
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+            .expect("The win mask cache should not be poisoned.");
+        let masks = cache.entry(spec).or_insert_with(|| {
+            let all: Vec<u128> = spec
+                .lines()
+                .iter()
+                .map(|line| {
+                    line.iter()
+                        .fold(0_u128, |acc, &(r, c)| acc | 1 << (r * spec.cols + c))
+                })
+                .collect();
+            let by_cell = (0..spec.cells())
+                .map(|i| all.iter().copied().filter(|m| m & 1 << i != 0).collect())
+                .collect();
+            Arc::new(WinMasks { all, by_cell })
+        });
+        Arc::clone(masks)
+    }
+}
+
+impl Iterator for BitMoves {
+    type Item = (usize, usize);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.empty == 0 {
+            return None;
+        }
+        let i = self.empty.trailing_zeros() as usize;
+        self.empty &= self.empty - 1;
+        Some((i / self.cols, i % self.cols))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.empty.count_ones() as usize;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for BitMoves {}
+
+impl PartialEq for BitBoard {
+    fn eq(&self, other: &Self) -> bool {
+        self.crosses == other.crosses && self.noughts == other.noughts && self.spec == other.spec
+    }
+}
+
+impl Eq for BitBoard {}
+
+impl Hash for BitBoard {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.crosses.hash(state);
+        self.noughts.hash(state);
+        self.spec.hash(state);
+    }
+}
+
+impl Default for BitBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BitBoard {
+    pub fn new() -> Self {
+        Self::with_spec(BoardSpec::default())
+    }
+    // A bitboard holds at most 128 squares; larger boards stay on the char array.
+    pub fn fits(spec: &BoardSpec) -> bool {
+        spec.cells() <= 128
+    }
+    pub fn with_spec(spec: BoardSpec) -> Self {
+        assert!(Self::fits(&spec), "A bitboard holds at most 128 squares.");
+        BitBoard {
+            crosses: 0,
+            noughts: 0,
+            spec,
+            masks: WinMasks::for_spec(spec),
+        }
+    }
+    pub fn index(&self, mv: &(usize, usize)) -> usize {
+        mv.0 * self.spec.cols + mv.1
+    }
+    pub fn full_mask(&self) -> u128 {
+        if self.spec.cells() == 128 {
+            u128::MAX
+        } else {
+            (1 << self.spec.cells()) - 1
+        }
+    }
+    pub fn empty_mask(&self) -> u128 {
+        self.full_mask() & !(self.crosses | self.noughts)
+    }
+    pub fn marks(&self, mark: &Marks) -> u128 {
+        match mark {
+            Marks::CROSS => self.crosses,
+            _ => self.noughts,
+        }
+    }
+    pub fn get(&self, mv: &(usize, usize)) -> char {
+        let bit = 1 << self.index(mv);
+        if self.crosses & bit != 0 {
+            Marks::CROSS.as_char()
+        } else if self.noughts & bit != 0 {
+            Marks::NOUGHT.as_char()
+        } else {
+            '-'
+        }
+    }
+    pub fn play(&mut self, mv: &(usize, usize), mark: &Marks) {
+        let bit = 1 << self.index(mv);
+        match mark {
+            Marks::CROSS => self.crosses |= bit,
+            _ => self.noughts |= bit,
+        }
+    }
+    pub fn undo(&mut self, mv: &(usize, usize)) {
+        let bit = !(1 << self.index(mv));
+        self.crosses &= bit;
+        self.noughts &= bit;
+    }
+    pub fn side_to_move(&self) -> Marks {
+        if self.crosses.count_ones() > self.noughts.count_ones() {
+            Marks::NOUGHT
+        } else {
+            Marks::CROSS
+        }
+    }
+    pub fn available_moves(&self) -> BitMoves {
+        BitMoves {
+            empty: self.empty_mask(),
+            cols: self.spec.cols,
+        }
+    }
+    pub fn is_full(&self) -> bool {
+        self.empty_mask() == 0
+    }
+    pub fn is_win(&self, mark: &Marks) -> bool {
+        let bits = self.marks(mark);
+        self.masks.all.iter().copied().any(|m| bits & m == m)
+    }
+    pub fn is_win_through(&self, mark: &Marks, mv: &(usize, usize)) -> bool {
+        let bits = self.marks(mark);
+        self.masks.by_cell[self.index(mv)]
+            .iter()
+            .copied()
+            .any(|m| bits & m == m)
+    }
+    pub fn is_game_over(&self, mark: &Marks) -> IsGameOver {
+        if self.is_win(mark) {
+            IsGameOver::Win
+        } else if self.is_full() {
+            IsGameOver::Drawn
+        } else {
+            IsGameOver::InPlay
+        }
+    }
+    pub fn to_state_key(&self) -> String {
+        let key: Vec<u8> = (0..self.spec.cells())
+            .map(|i| match ((self.crosses >> i) & 1, (self.noughts >> i) & 1) {
+                (1, _) => b'X',
+                (_, 1) => b'0',
+                _ => b'-',
+            })
+            .collect();
+        String::from_utf8(key).expect("The state key is always ASCII.")
+    }
+    pub fn to_game_state(&self) -> GameState {
+        let mut state = GameState::with_spec(self.spec);
+        for ((r, c), value) in state.indexed_iter_mut() {
+            *value = self.get(&(r, c));
+        }
+        state
+    }
+}
+
+impl From<&GameState> for BitBoard {
+    fn from(state: &GameState) -> Self {
+        let mut bits = BitBoard::with_spec(state.spec);
+        for (i, value) in state.iter().enumerate() {
+            if *value == Marks::CROSS.as_char() {
+                bits.crosses |= 1 << i;
+            } else if *value == Marks::NOUGHT.as_char() {
+                bits.noughts |= 1 << i;
+            }
+        }
+        bits
+    }
+}
+
+impl From<&BitBoard> for GameState {
+    fn from(bits: &BitBoard) -> Self {
+        bits.to_game_state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_bitboard_working() {
+        let mut state = GameState::new();
+        state.state[[0, 0]] = 'X';
+        state.state[[1, 1]] = '0';
+        state.state[[2, 1]] = 'X';
+        let mut bits = state.to_bitboard().unwrap();
+        assert_eq!(bits.to_state_key(), state.to_state_key());
+        assert_eq!(bits.available_moves().collect::<Vec<_>>(), state.available_moves());
+        assert_eq!(bits.side_to_move(), Marks::NOUGHT);
+        bits.play(&(0, 2), &Marks::NOUGHT);
+        bits.play(&(2, 0), &Marks::NOUGHT);
+        assert!(bits.is_win_through(&Marks::NOUGHT, &(2, 0)));
+        assert_eq!(bits.is_game_over(&Marks::NOUGHT), IsGameOver::Win);
+        assert_eq!(bits.is_game_over(&Marks::CROSS), IsGameOver::InPlay);
+        let back = bits.to_game_state();
+        assert_eq!(back.is_game_over(&Marks::NOUGHT), IsGameOver::Win);
+        bits.undo(&(2, 0));
+        assert_eq!(bits.is_game_over(&Marks::NOUGHT), IsGameOver::InPlay);
+    }
+
+    #[test]
+    fn is_bitboard_spec_working() {
+        let spec = BoardSpec::new(6, 7, 4);
+        let mut bits = BitBoard::with_spec(spec);
+        assert_eq!(WinMasks::for_spec(spec).all.len(), 69);
+        assert_eq!(bits.available_moves().len(), 42);
+        for c in 2..6 {
+            bits.play(&(5, c), &Marks::CROSS);
+        }
+        assert_eq!(bits.is_game_over(&Marks::CROSS), IsGameOver::Win);
+        assert_eq!(bits.to_game_state().is_game_over(&Marks::CROSS), IsGameOver::Win);
+    }
+}
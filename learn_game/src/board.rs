@@ -1,6 +1,8 @@
+use crate::bitboard::BitBoard;
 use crate::players::Marks;
-use itertools::Itertools;
+use crate::symmetry::Symmetry;
 use ndarray::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{
     fmt,
     ops::{Deref, DerefMut},
@@ -13,9 +15,17 @@ pub enum IsGameOver {
     Win,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BoardSpec {
+    pub rows: usize,
+    pub cols: usize,
+    pub win_len: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct GameState {
     pub state: Array<char, Dim<[usize; 2]>>,
+    pub spec: BoardSpec,
 }
 
 #[derive(Debug)]
@@ -23,6 +33,52 @@ pub struct Board {
     pub previous_state: GameState,
     pub current_state: GameState,
     pub next_state: GameState,
+    // Mirrors `next_state` for the training loop when the board fits in a bitboard.
+    pub bits: Option<BitBoard>,
+}
+
+impl BoardSpec {
+    pub fn new(rows: usize, cols: usize, win_len: usize) -> Self {
+        assert!(rows > 0 && cols > 0, "The board should have at least one square.");
+        assert!(
+            win_len > 0 && win_len <= rows.max(cols),
+            "The win length should fit on the board."
+        );
+        BoardSpec { rows, cols, win_len }
+    }
+    pub fn square(size: usize) -> Self {
+        Self::new(size, size, size)
+    }
+    pub fn cells(&self) -> usize {
+        self.rows * self.cols
+    }
+    pub fn lines(&self) -> Vec<Vec<(usize, usize)>> {
+        let k = self.win_len as isize;
+        let (rows, cols) = (self.rows as isize, self.cols as isize);
+        let mut lines = Vec::new();
+        for (dr, dc) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+            for r in 0..rows {
+                for c in 0..cols {
+                    let (end_r, end_c) = (r + dr * (k - 1), c + dc * (k - 1));
+                    if end_r < 0 || end_r >= rows || end_c < 0 || end_c >= cols {
+                        continue;
+                    }
+                    lines.push(
+                        (0..k)
+                            .map(|i| ((r + dr * i) as usize, (c + dc * i) as usize))
+                            .collect(),
+                    );
+                }
+            }
+        }
+        lines
+    }
+}
+
+impl Default for BoardSpec {
+    fn default() -> Self {
+        BoardSpec::square(3)
+    }
 }
 
 impl Deref for GameState {
@@ -44,23 +100,43 @@ impl fmt::Display for GameState {
     }
 }
 
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GameState {
     pub fn new() -> Self {
+        Self::with_spec(BoardSpec::default())
+    }
+    pub fn with_spec(spec: BoardSpec) -> Self {
         GameState {
-            state: Array::from_elem((3, 3), '-'),
+            state: Array::from_elem((spec.rows, spec.cols), '-'),
+            spec,
         }
     }
     pub fn to_state_key(&self) -> String {
-        let state: String = self.state.clone().into_iter().collect::<String>();
-        state
+        self.state.iter().collect::<String>()
+    }
+    pub fn to_bitboard(&self) -> Option<BitBoard> {
+        BitBoard::fits(&self.spec).then(|| BitBoard::from(self))
+    }
+    pub fn canonicalize(&self) -> (String, Symmetry) {
+        Symmetry::all(&self.spec)
+            .iter()
+            .map(|sym| (sym.transform_key(self), *sym))
+            .min_by(|a, b| a.0.cmp(&b.0))
+            .unwrap()
     }
     pub fn draw(&self) {
-        println!("* * * * *");
-        self.to_string()
-            .chars()
-            .tuples::<(_, _, _)>()
-            .for_each(|a| println!("* {} {} {} *", a.0, a.1, a.2));
-        println!("* * * * *");
+        let border = vec!["*"; self.spec.cols + 2].join(" ");
+        println!("{border}");
+        for row in self.rows() {
+            let squares = row.iter().map(char::to_string).collect::<Vec<_>>().join(" ");
+            println!("* {squares} *");
+        }
+        println!("{border}");
     }
     pub fn available_moves(&self) -> Vec<(usize, usize)> {
         (*self)
@@ -82,68 +158,102 @@ impl GameState {
         }
     }
     pub fn is_full(&self) -> bool {
-        let v = (*self)
-            .indexed_iter()
-            .filter(|(_index, &value)| value == '-')
-            .map(|(index, _)| index)
-            .collect::<Vec<(usize, usize)>>();
-        v.is_empty()
+        self.iter().all(|&value| value != '-')
+    }
+    pub fn side_to_move(&self) -> Marks {
+        let count = |mark: Marks| self.iter().filter(|&&value| value == mark.as_char()).count();
+        if count(Marks::CROSS) > count(Marks::NOUGHT) {
+            Marks::NOUGHT
+        } else {
+            Marks::CROSS
+        }
+    }
+}
+
+impl PartialEq for GameState {
+    fn eq(&self, other: &Self) -> bool {
+        self.spec == other.spec && self.state == other.state
     }
 }
 
 impl PartialEq<char> for GameState {
     fn eq(&self, other: &char) -> bool {
-        for row in self.rows() {
-            let accum = row.fold(true, |acc, x| acc && (x == other));
-            if accum == true {
-                return true;
-            }
-        }
-        for column in self.columns() {
-            let accum = column.fold(true, |acc, x| acc && (x == other));
-            if accum == true {
-                return true;
+        let k = self.spec.win_len;
+        for (dr, dc) in [(0_isize, 1_isize), (1, 0), (1, 1), (1, -1)] {
+            for ((r, c), value) in self.indexed_iter() {
+                if value != other {
+                    continue;
+                }
+                let in_a_row = (1..k as isize)
+                    .take_while(|&i| {
+                        let (nr, nc) = (r as isize + dr * i, c as isize + dc * i);
+                        nr >= 0
+                            && nc >= 0
+                            && self.get([nr as usize, nc as usize]) == Some(other)
+                    })
+                    .count();
+                if in_a_row + 1 >= k {
+                    return true;
+                }
             }
         }
-        let accum = self.diag().fold(true, |acc, x| acc && (x == other));
-        if accum == true {
-            return true;
-        }
-        if self[[0, 2]] == *other && self[[1, 1]] == *other && self[[2, 0]] == *other {
-            return true;
-        }
         false
     }
 }
 
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Board {
     pub fn new() -> Self {
+        Self::with_spec(BoardSpec::default())
+    }
+
+    pub fn with_spec(spec: BoardSpec) -> Self {
         Board {
-            previous_state: GameState::new(),
-            current_state: GameState::new(),
-            next_state: GameState::new(),
+            previous_state: GameState::with_spec(spec),
+            current_state: GameState::with_spec(spec),
+            next_state: GameState::with_spec(spec),
+            bits: BitBoard::fits(&spec).then(|| BitBoard::with_spec(spec)),
         }
     }
 
+    pub fn spec(&self) -> BoardSpec {
+        self.next_state.spec
+    }
+
     pub fn is_full(&self) -> bool {
-        let v = (*self.next_state)
-            .indexed_iter()
-            .filter(|(_index, &value)| value == '-')
-            .map(|(index, _)| index)
-            .collect::<Vec<(usize, usize)>>();
-        v.is_empty()
+        self.next_state.is_full()
     }
 
     pub fn is_game_over(&self, mark: &Marks) -> IsGameOver {
-        match self.next_state == mark.as_char() {
-            true => IsGameOver::Win,
-            false => {
-                if self.is_full() {
-                    IsGameOver::Drawn
-                } else {
-                    IsGameOver::InPlay
-                }
-            }
+        self.next_state.is_game_over(mark)
+    }
+
+    // Plays a move a player has just made on `next_state` onto the bitboard as well.
+    pub fn track_move(&mut self, mv: &(usize, usize), mark: &Marks) {
+        if let Some(bits) = self.bits.as_mut() {
+            bits.play(mv, mark);
+        }
+    }
+
+    // Only lines through the last move can have been completed by it.
+    pub fn is_game_over_after(&self, mv: &(usize, usize), mark: &Marks) -> IsGameOver {
+        match &self.bits {
+            Some(bits) if bits.is_win_through(mark, mv) => IsGameOver::Win,
+            Some(bits) if bits.is_full() => IsGameOver::Drawn,
+            Some(_) => IsGameOver::InPlay,
+            None => self.is_game_over(mark),
+        }
+    }
+
+    pub fn available_moves(&self) -> Vec<(usize, usize)> {
+        match &self.bits {
+            Some(bits) => bits.available_moves().collect(),
+            None => self.next_state.available_moves(),
         }
     }
 }
@@ -178,4 +288,53 @@ mod tests {
         let t: IsGameOver = test_board.is_game_over(&mark);
         assert_eq!(IsGameOver::Win, t);
     }
+
+    #[test]
+    fn is_board_spec_working() {
+        let spec = BoardSpec::new(6, 7, 4);
+        let mut board = Board::with_spec(spec);
+        assert_eq!(board.next_state.available_moves().len(), 42);
+        assert_eq!(spec.lines().len(), 69);
+        board.next_state.state[[5, 1]] = 'X';
+        board.next_state.state[[4, 2]] = 'X';
+        board.next_state.state[[3, 3]] = 'X';
+        assert_eq!(board.is_game_over(&Marks::CROSS), IsGameOver::InPlay);
+        board.next_state.state[[2, 4]] = 'X';
+        assert_eq!(board.is_game_over(&Marks::CROSS), IsGameOver::Win);
+        assert_eq!(board.is_game_over(&Marks::NOUGHT), IsGameOver::InPlay);
+
+        let mut board = Board::with_spec(spec);
+        for (i, mv) in [(5, 1), (0, 0), (4, 2), (0, 1), (3, 3), (0, 2)].iter().enumerate() {
+            let mark = if i % 2 == 0 { Marks::CROSS } else { Marks::NOUGHT };
+            board.next_state[[mv.0, mv.1]] = mark.as_char();
+            board.track_move(mv, &mark);
+            assert_eq!(board.is_game_over_after(mv, &mark), IsGameOver::InPlay);
+        }
+        assert_eq!(board.available_moves(), board.next_state.available_moves());
+        board.next_state[[2, 4]] = 'X';
+        board.track_move(&(2, 4), &Marks::CROSS);
+        assert_eq!(board.is_game_over_after(&(2, 4), &Marks::CROSS), IsGameOver::Win);
+
+        let mut state = GameState::with_spec(BoardSpec::square(4));
+        for c in 0..3 {
+            state.state[[2, c]] = '0';
+        }
+        assert_eq!(state.is_game_over(&Marks::NOUGHT), IsGameOver::InPlay);
+        state.state[[2, 3]] = '0';
+        assert_eq!(state.is_game_over(&Marks::NOUGHT), IsGameOver::Win);
+        state.draw();
+
+        // Too many squares for a bitboard, so the board keeps to the char array.
+        let mut board = Board::with_spec(BoardSpec::new(12, 12, 5));
+        assert!(board.bits.is_none() && board.next_state.to_bitboard().is_none());
+        assert!(!board.is_full());
+        for c in 7..12 {
+            board.next_state.state[[11, c]] = 'X';
+            board.track_move(&(11, c), &Marks::CROSS);
+        }
+        assert_eq!(board.is_game_over(&Marks::CROSS), IsGameOver::Win);
+        assert_eq!(board.is_game_over_after(&(11, 11), &Marks::CROSS), IsGameOver::Win);
+        assert_eq!(board.available_moves().len(), 139);
+        assert_eq!(board.next_state.side_to_move(), Marks::NOUGHT);
+    }
 }
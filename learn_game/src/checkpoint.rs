@@ -0,0 +1,37 @@
+use crate::board::BoardSpec;
+use crate::config::TrainingConfig;
+use crate::double_q::DoubleQTable;
+use crate::q_table::QTable;
+use crate::rng::GameRng;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub episode: usize,
+    pub spec: BoardSpec,
+    pub config: TrainingConfig,
+    pub rng: GameRng,
+    pub q: QTable,
+    #[serde(default)]
+    pub double_q: Option<DoubleQTable>,
+}
+
+impl Checkpoint {
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        // Write next to the target and rename, so a killed job never leaves a truncated file.
+        let tmp = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
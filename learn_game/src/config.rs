@@ -1,10 +1,298 @@
-use std::cell::{Cell, RefCell};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
 
-thread_local! {
-    pub static EXPLORATION_RATE: RefCell<f32> = RefCell::new(0.9_f32);
-    pub static LEARNING_RATE: RefCell<f32> = RefCell::new(0.1_f32);
-    pub static DISCOUNT_RATE: RefCell<f32> = RefCell::new(0.9_f32);
-    pub static K: RefCell<f32> = RefCell::new(0.05_f32);
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Schedule {
+    Constant {
+        value: f32,
+    },
+    // Adds `delta` on every multiple of `every` past episode `after`, stopping at `limit`; `every: 0` never steps.
+    Step {
+        start: f32,
+        delta: f32,
+        after: usize,
+        every: usize,
+        #[serde(default)]
+        limit: Option<f32>,
+    },
+    Linear {
+        start: f32,
+        end: f32,
+        episodes: usize,
+    },
+    Exponential {
+        start: f32,
+        decay: f32,
+        min: f32,
+    },
 }
 
-pub const NUM_EPISODES: usize = 500_000_usize;
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    #[default]
+    QLearning,
+    Sarsa,
+    ExpectedSarsa,
+    DoubleQLearning,
+    TdLambda,
+    MonteCarlo,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceKind {
+    #[default]
+    Replacing,
+    Accumulating,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VisitKind {
+    #[default]
+    FirstVisit,
+    EveryVisit,
+}
+
+// How the RL players pick moves outside the softmax phase.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Exploration {
+    #[default]
+    EpsilonGreedy,
+    // Greedy on Q plus c * sqrt(ln N(s) / N(s, a)); untried moves go first.
+    Ucb {
+        c: f32,
+    },
+    // Greedy, with new entries starting at `value` for the player to move.
+    Optimistic {
+        value: f32,
+    },
+    // Epsilon shrinks as epsilon * c / (c + N(s)) and exploration favours the least tried moves.
+    CountBased {
+        c: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hyperparameters {
+    pub epsilon: f32,
+    pub alpha: f32,
+    pub gamma: f32,
+    pub temperature: f32,
+    pub exploration: Exploration,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrainingConfig {
+    pub num_episodes: usize,
+    pub seed: Option<u64>,
+    pub algorithm: Algorithm,
+    pub lambda: f32,
+    pub traces: TraceKind,
+    pub visits: VisitKind,
+    pub softmax_after: usize,
+    pub exploration: Exploration,
+    pub epsilon: Schedule,
+    pub alpha: Schedule,
+    pub gamma: Schedule,
+    pub temperature: Schedule,
+    pub checkpoint_every: Option<usize>,
+    pub checkpoint_path: PathBuf,
+    pub eval_every: Option<usize>,
+    pub eval_games: usize,
+    pub metrics_every: Option<usize>,
+    pub metrics_path: PathBuf,
+    // Checks for the end of the game and lists moves on a bitboard wherever the board fits in one.
+    pub bitboard: bool,
+}
+
+impl Schedule {
+    pub fn value(&self, episode: usize) -> f32 {
+        match *self {
+            Schedule::Constant { value } => value,
+            Schedule::Step {
+                start,
+                delta,
+                after,
+                every,
+                limit,
+            } => {
+                let steps = if every > 0 && episode > after {
+                    episode / every - after / every
+                } else {
+                    0
+                };
+                let value = start + delta * steps as f32;
+                match limit {
+                    Some(limit) if delta < 0.0 => value.max(limit),
+                    Some(limit) => value.min(limit),
+                    None => value,
+                }
+            }
+            Schedule::Linear {
+                start,
+                end,
+                episodes,
+            } => {
+                let t = (episode as f32 / episodes.max(1) as f32).min(1.0);
+                start + (end - start) * t
+            }
+            Schedule::Exponential { start, decay, min } => {
+                (start * decay.powi(episode as i32)).max(min)
+            }
+        }
+    }
+    pub fn validate(&self, name: &str) -> Result<(), anyhow::Error> {
+        match *self {
+            Schedule::Step { every: 0, .. } => {
+                Err(anyhow::anyhow!("The {name} step schedule needs `every` above 0."))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for Hyperparameters {
+    fn default() -> Self {
+        TrainingConfig::default().at(0)
+    }
+}
+
+impl Hyperparameters {
+    pub fn greedy() -> Self {
+        Hyperparameters {
+            epsilon: 0.0,
+            exploration: Exploration::EpsilonGreedy,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        TrainingConfig {
+            num_episodes: 500_000,
+            seed: None,
+            algorithm: Algorithm::QLearning,
+            lambda: 0.8,
+            traces: TraceKind::Replacing,
+            visits: VisitKind::FirstVisit,
+            softmax_after: 200_000,
+            exploration: Exploration::EpsilonGreedy,
+            epsilon: Schedule::Step {
+                start: 0.9,
+                delta: -0.1,
+                after: 100_000,
+                every: 10_000,
+                limit: Some(0.1),
+            },
+            alpha: Schedule::Constant { value: 0.1 },
+            gamma: Schedule::Constant { value: 0.9 },
+            // Annealed from exploratory to nearly greedy once softmax sampling starts.
+            temperature: Schedule::Step {
+                start: 1.0,
+                delta: -0.05,
+                after: 200_000,
+                every: 10_000,
+                limit: Some(0.05),
+            },
+            checkpoint_every: None,
+            checkpoint_path: PathBuf::from("./q_table_archive/checkpoint.json"),
+            eval_every: None,
+            eval_games: 200,
+            metrics_every: None,
+            metrics_path: PathBuf::from("./q_table_archive/metrics.jsonl"),
+            bitboard: true,
+        }
+    }
+}
+
+impl TrainingConfig {
+    pub fn at(&self, episode: usize) -> Hyperparameters {
+        Hyperparameters {
+            epsilon: self.epsilon.value(episode),
+            alpha: self.alpha.value(episode),
+            gamma: self.gamma.value(episode),
+            temperature: self.temperature.value(episode),
+            exploration: self.exploration,
+        }
+    }
+    pub fn from_file(path: &Path) -> Result<Self, anyhow::Error> {
+        let text = fs::read_to_string(path)?;
+        let config: TrainingConfig = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&text)?,
+            _ => serde_json::from_str(&text)?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        self.epsilon.validate("epsilon")?;
+        self.alpha.validate("alpha")?;
+        self.gamma.validate("gamma")?;
+        self.temperature.validate("temperature")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_schedule_working() {
+        let config = TrainingConfig::default();
+        assert_eq!(config.epsilon.value(100_000), 0.9);
+        assert!((config.epsilon.value(119_999) - 0.8).abs() < 1e-6);
+        assert_eq!(config.epsilon.value(400_000), 0.1);
+        assert_eq!(config.temperature.value(200_000), 1.0);
+        assert!((config.temperature.value(210_000) - 0.95).abs() < 1e-6);
+        assert_eq!(config.temperature.value(500_000), 0.05);
+        let linear = Schedule::Linear {
+            start: 1.0,
+            end: 0.0,
+            episodes: 10,
+        };
+        assert_eq!((linear.value(5), linear.value(20)), (0.5, 0.0));
+        let exponential = Schedule::Exponential {
+            start: 1.0,
+            decay: 0.5,
+            min: 0.2,
+        };
+        assert_eq!((exponential.value(1), exponential.value(10)), (0.5, 0.2));
+    }
+
+    #[test]
+    fn is_config_from_file_working() {
+        let text = r#"
+            num_episodes = 1000
+            seed = 7
+            epsilon = { kind = "linear", start = 0.5, end = 0.0, episodes = 1000 }
+        "#;
+        let config: TrainingConfig = toml::from_str(text).unwrap();
+        assert_eq!((config.num_episodes, config.seed), (1000, Some(7)));
+        assert_eq!(config.at(500).epsilon, 0.25);
+        assert_eq!(config.alpha, TrainingConfig::default().alpha);
+        let json = serde_json::to_string(&config).unwrap();
+        let path = std::env::temp_dir().join("learn_game_config_test.json");
+        fs::write(&path, json).unwrap();
+        assert_eq!(TrainingConfig::from_file(&path).unwrap(), config);
+
+        let path = std::env::temp_dir().join("learn_game_config_test.toml");
+        fs::write(&path, r#"alpha = { kind = "step", start = 0.5, delta = -0.1, after = 0, every = 0 }"#).unwrap();
+        let error = TrainingConfig::from_file(&path).unwrap_err();
+        assert!(error.to_string().contains("alpha"), "{error}");
+        let never = Schedule::Step {
+            start: 0.5,
+            delta: -0.1,
+            after: 0,
+            every: 0,
+            limit: None,
+        };
+        assert_eq!(never.value(1_000), 0.5);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::config::Hyperparameters;
+use crate::q_table::{QTable, Transition};
+use crate::rng::GameRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// Two independent estimates; the combined table handed to the players holds their average.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DoubleQTable {
+    pub first: QTable,
+    pub second: QTable,
+}
+
+impl DoubleQTable {
+    pub fn new(combined: &QTable) -> Self {
+        let empty = || {
+            if combined.is_symmetric() {
+                QTable::with_symmetry()
+            } else {
+                QTable::new()
+            }
+        };
+        DoubleQTable {
+            first: empty(),
+            second: empty(),
+        }
+    }
+
+    // Seeds both halves from the combined table the first time a state is seen.
+    pub fn track(&mut self, combined: &QTable, key: &str) {
+        if !self.first.contains_key(key) {
+            let moves = combined.get(key).expect("The combined table should hold the key.");
+            self.first.insert(key.to_owned(), moves.clone());
+            self.second.insert(key.to_owned(), moves.clone());
+        }
+    }
+
+    // One half picks the reply, the other one values it.
+    pub fn target(&self, next_state_key: &str, player: &str, first_selects: bool) -> f32 {
+        let (select, evaluate) = if first_selects {
+            (&self.first, &self.second)
+        } else {
+            (&self.second, &self.first)
+        };
+        let moves = select.get(next_state_key).expect("The next state key should be present.");
+        let best = if player == "RLmin" {
+            moves.iter().max_by(|a, b| a.1.total_cmp(b.1))
+        } else {
+            moves.iter().min_by(|a, b| a.1.total_cmp(b.1))
+        };
+        evaluate.get(next_state_key).unwrap()[best.unwrap().0]
+    }
+
+    // A coin flip decides which half picks the reply and which one values it, and which half learns.
+    pub fn learn(&mut self, combined: &mut QTable, rng: &mut GameRng, t: &Transition, params: &Hyperparameters) -> f32 {
+        let first_selects = rng.gen_bool(0.5);
+        let target = t.target(params.gamma, |next| self.target(next, t.player, first_selects));
+        self.update_toward(combined, first_selects, t.key, &t.mv, target, params)
+    }
+
+    pub fn update_toward(
+        &mut self,
+        combined: &mut QTable,
+        first_selects: bool,
+        current_state_key: &str,
+        current_move: &(usize, usize),
+        expected: f32,
+        params: &Hyperparameters,
+    ) -> f32 {
+        let select = if first_selects {
+            &mut self.first
+        } else {
+            &mut self.second
+        };
+        let td_error = select.update_toward(current_state_key, current_move, expected, params);
+        let average = (self.first.get(current_state_key).unwrap()[current_move]
+            + self.second.get(current_state_key).unwrap()[current_move])
+            / 2.0;
+        *combined
+            .get_mut(current_state_key)
+            .unwrap()
+            .get_mut(current_move)
+            .unwrap() = average;
+        td_error
+    }
+
+    pub fn combined(&self) -> QTable {
+        let mut combined = self.first.clone();
+        for (key, moves) in combined.iter_mut() {
+            let other = self.second.get(key).unwrap();
+            for (mv, value) in moves.iter_mut() {
+                *value = (*value + other[mv]) / 2.0;
+            }
+        }
+        combined
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Algorithm, TrainingConfig};
+    use crate::q_table::Moves;
+    use crate::tests::seeded_game;
+
+    #[test]
+    fn is_double_q_table_working() {
+        let mut combined = QTable::new();
+        combined.insert("now".to_string(), Moves::from(vec![(0, 0)]));
+        combined.insert("next".to_string(), Moves::from(vec![(1, 0), (1, 1)]));
+        let mut double = DoubleQTable::new(&combined);
+        double.track(&combined, "now");
+        double.track(&combined, "next");
+        *double.first.get_mut("next").unwrap().get_mut(&(1, 0)).unwrap() = -1.0;
+        *double.first.get_mut("next").unwrap().get_mut(&(1, 1)).unwrap() = 1.0;
+        *double.second.get_mut("next").unwrap().get_mut(&(1, 0)).unwrap() = 0.5;
+        *double.second.get_mut("next").unwrap().get_mut(&(1, 1)).unwrap() = 0.25;
+        // RLmax's reply comes from RLmin, who picks the minimum of the selecting half.
+        assert_eq!(double.target("next", "RLmax", true), 0.5);
+        assert_eq!(double.target("next", "RLmax", false), 1.0);
+        assert_eq!(double.target("next", "RLmin", true), 0.25);
+
+        let params = Hyperparameters {
+            alpha: 0.5,
+            ..Hyperparameters::default()
+        };
+        let td_error = double.update_toward(&mut combined, true, "now", &(0, 0), 1.0, &params);
+        assert_eq!(td_error, 101.0);
+        assert_eq!(double.first.get("now").unwrap()[&(0, 0)], -49.5);
+        assert_eq!(double.second.get("now").unwrap()[&(0, 0)], -100.0);
+        assert_eq!(combined.get("now").unwrap()[&(0, 0)], -74.75);
+        assert_eq!(double.combined().get("now").unwrap()[&(0, 0)], -74.75);
+    }
+
+    #[test]
+    fn is_double_q_learning_working() {
+        let (mut game, mut q) = seeded_game(TrainingConfig {
+            seed: Some(4),
+            algorithm: Algorithm::DoubleQLearning,
+            ..TrainingConfig::default()
+        });
+        for _ in 0..300 {
+            game.learn_episode(Some(&mut q));
+        }
+        let double = game.double_q.as_ref().unwrap();
+        assert_eq!(double.first.len(), q.len());
+        assert!(double.first.is_symmetric());
+        let combined = double.combined();
+        assert!(q.iter().all(|(key, moves)| moves
+            .iter()
+            .all(|(mv, value)| (combined.get(key).unwrap()[mv] - value).abs() < 1e-6)));
+        let differs = q.iter().any(|(key, moves)| moves.moves != double.first.get(key).unwrap().moves);
+        assert!(differs);
+    }
+}
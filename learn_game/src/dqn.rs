@@ -0,0 +1,336 @@
+use crate::board::{Board, BoardSpec, GameState};
+use crate::config::{Hyperparameters, TrainingConfig};
+use crate::env::TicTacToeEnv;
+use crate::metrics::{EpisodeStats, MetricsLog};
+use crate::players::{Marks, Player};
+use crate::q_table::QTable;
+use crate::rng::{self, GameRng};
+use crate::mlp::Mlp;
+use ndarray::{Array1, Array2, ArrayView1};
+use rand::Rng;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DqnConfig {
+    pub hidden: usize,
+    pub learning_rate: f32,
+    pub batch_size: usize,
+    pub replay_capacity: usize,
+    // Steps between copies of the online network into the target network.
+    pub target_sync_every: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Transition {
+    pub state: Array1<f32>,
+    pub action: usize,
+    pub reward: f32,
+    pub next_state: Array1<f32>,
+    pub next_mask: Vec<bool>,
+    pub done: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayBuffer {
+    pub capacity: usize,
+    transitions: VecDeque<Transition>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DqnAgent {
+    pub config: DqnConfig,
+    pub net: Mlp,
+    pub target: Mlp,
+    pub replay: ReplayBuffer,
+    steps: usize,
+}
+
+#[derive(Debug)]
+pub struct DqnPlayer {
+    pub name: String,
+    pub mark: Marks,
+    pub params: Hyperparameters,
+    pub net: Mlp,
+}
+
+impl Default for DqnConfig {
+    fn default() -> Self {
+        DqnConfig {
+            hidden: 64,
+            learning_rate: 0.01,
+            batch_size: 32,
+            replay_capacity: 10_000,
+            target_sync_every: 500,
+        }
+    }
+}
+
+// Three planes over the squares: the player's own marks, the opponent's, and the empty ones.
+pub fn one_hot(state: &GameState, mark: Marks) -> Array1<f32> {
+    let (mine, theirs) = (mark.as_char(), mark.other().as_char());
+    let cells = state.spec.cells();
+    let mut x = Array1::zeros(3 * cells);
+    for (i, &c) in state.iter().enumerate() {
+        let plane = if c == mine {
+            0
+        } else if c == theirs {
+            1
+        } else {
+            2
+        };
+        x[plane * cells + i] = 1.0;
+    }
+    x
+}
+
+// The legal move with the highest value.
+pub fn best_action(values: ArrayView1<f32>, mask: &[bool]) -> Option<usize> {
+    values
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| mask[*i])
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, _)| i)
+}
+
+// A random legal move with probability epsilon, otherwise the one the network values most.
+pub fn epsilon_greedy(net: &Mlp, state: &Array1<f32>, mask: &[bool], epsilon: f32, rng: &mut GameRng) -> usize {
+    if rng.gen_range(0_f32..=1_f32) < epsilon {
+        let legal: Vec<usize> = (0..mask.len()).filter(|&i| mask[i]).collect();
+        legal[rng.gen_range(0..legal.len())]
+    } else {
+        best_action(net.forward(state).view(), mask).unwrap()
+    }
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> Self {
+        ReplayBuffer {
+            capacity,
+            transitions: VecDeque::with_capacity(capacity),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+    // The oldest transition makes room once the buffer is full.
+    pub fn push(&mut self, transition: Transition) {
+        if self.transitions.len() == self.capacity {
+            self.transitions.pop_front();
+        }
+        self.transitions.push_back(transition);
+    }
+    pub fn sample(&self, n: usize, rng: &mut GameRng) -> Vec<&Transition> {
+        (0..n)
+            .map(|_| &self.transitions[rng.gen_range(0..self.transitions.len())])
+            .collect()
+    }
+}
+
+impl DqnAgent {
+    pub fn new(spec: BoardSpec, config: DqnConfig, rng: &mut GameRng) -> Self {
+        let net = Mlp::new(&[3 * spec.cells(), config.hidden, config.hidden, spec.cells()], rng);
+        DqnAgent {
+            config,
+            target: net.clone(),
+            net,
+            replay: ReplayBuffer::new(config.replay_capacity),
+            steps: 0,
+        }
+    }
+    pub fn act(&self, state: &Array1<f32>, mask: &[bool], epsilon: f32, rng: &mut GameRng) -> usize {
+        epsilon_greedy(&self.net, state, mask, epsilon, rng)
+    }
+    // Targets bootstrap from the target network, which lags behind so the regression target holds still.
+    // Returns the batch's mean absolute TD error and its Huber loss.
+    fn learn_batch(&mut self, gamma: f32, rng: &mut GameRng) -> (f32, f32) {
+        let batch = self.replay.sample(self.config.batch_size, rng);
+        let width = batch[0].state.len();
+        let mut inputs = Array2::zeros((batch.len(), width));
+        let mut next_inputs = Array2::zeros((batch.len(), width));
+        for (row, transition) in batch.iter().enumerate() {
+            inputs.row_mut(row).assign(&transition.state);
+            next_inputs.row_mut(row).assign(&transition.next_state);
+        }
+        let next_values = self.target.activations(&next_inputs).pop().unwrap();
+        let actions: Vec<usize> = batch.iter().map(|transition| transition.action).collect();
+        let targets = batch
+            .iter()
+            .zip(next_values.rows())
+            .map(|(transition, next)| match best_action(next, &transition.next_mask) {
+                Some(best) if !transition.done => transition.reward + gamma * next[best],
+                _ => transition.reward,
+            })
+            .collect();
+        self.net.train_batch(&inputs, &actions, &targets, self.config.learning_rate)
+    }
+    pub fn learn_episode(&mut self, env: &mut TicTacToeEnv, params: &Hyperparameters, rng: &mut GameRng) -> EpisodeStats {
+        let mut stats = EpisodeStats::default();
+        let mut obs = env.reset(None);
+        loop {
+            let state = one_hot(&obs.state, obs.agent_mark);
+            let action = self.act(&state, &env.legal_action_mask(), params.epsilon, rng);
+            let (next, reward, done, info) = env.step(action);
+            self.replay.push(Transition {
+                state,
+                action,
+                reward,
+                next_state: one_hot(&next.state, next.agent_mark),
+                next_mask: env.legal_action_mask(),
+                done,
+            });
+            if self.replay.len() >= self.config.batch_size {
+                let (td_error, loss) = self.learn_batch(params.gamma, rng);
+                stats.td_error += td_error;
+                stats.loss += loss;
+                stats.updates += 1;
+            }
+            self.steps += 1;
+            if self.steps.is_multiple_of(self.config.target_sync_every) {
+                self.target = self.net.clone();
+            }
+            if done {
+                stats.winner = info.winner;
+                return stats;
+            }
+            obs = next;
+        }
+    }
+    pub fn player(&self, name: String) -> DqnPlayer {
+        DqnPlayer {
+            name,
+            mark: Marks::None,
+            params: Hyperparameters::greedy(),
+            net: self.net.clone(),
+        }
+    }
+}
+
+pub fn train(env: &mut TicTacToeEnv, config: &TrainingConfig, dqn: DqnConfig) -> DqnAgent {
+    let mut rng = config.seed.map_or_else(rng::from_entropy, rng::seeded);
+    if let Some(seed) = config.seed {
+        env.reset(Some(seed));
+    }
+    let mut agent = DqnAgent::new(env.spec, dqn, &mut rng);
+    let mut metrics = MetricsLog::open(config, false);
+    for episode in 0..config.num_episodes {
+        let params = config.at(episode);
+        let stats = agent.learn_episode(env, &params, &mut rng);
+        // The network keeps no table, so the replay buffer stands in for its size.
+        metrics.record(episode + 1, &stats, agent.replay.len(), &params);
+    }
+    agent
+}
+
+impl Player for DqnPlayer {
+    fn set_mark(&mut self, mark: Marks) {
+        self.mark = mark;
+    }
+    fn get_mark(&self) -> &Marks {
+        &self.mark
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn choose_move(&self, board: &Board, _q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        let state = &board.current_state;
+        let mask: Vec<bool> = state.iter().map(|&c| c == '-').collect();
+        let action = epsilon_greedy(&self.net, &one_hot(state, self.mark), &mask, self.params.epsilon, rng);
+        (action / state.spec.cols, action % state.spec.cols)
+    }
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.choose_move(board, q, rng)
+    }
+    fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
+        let (a, b) = *mv;
+        *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
+    }
+    fn set_hyperparameters(&mut self, params: &Hyperparameters) {
+        self.params = *params;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Schedule;
+    use crate::env::RandomOpponent;
+    use crate::evaluate::{self, EvalOptions};
+    use crate::players::RandomPlayer;
+
+    #[test]
+    fn is_replay_buffer_working() {
+        let mut rng = rng::seeded(0);
+        let mut replay = ReplayBuffer::new(2);
+        for action in 0..3 {
+            replay.push(Transition {
+                state: Array1::zeros(1),
+                action,
+                reward: 0.0,
+                next_state: Array1::zeros(1),
+                next_mask: vec![],
+                done: true,
+            });
+        }
+        assert_eq!(replay.len(), 2);
+        assert!(replay.sample(10, &mut rng).iter().all(|t| t.action > 0));
+        assert_eq!(best_action(ArrayView1::from(&[3.0, 1.0, 2.0]), &[false, true, true]), Some(2));
+    }
+
+    #[test]
+    fn is_dqn_working() {
+        let spec = BoardSpec::default();
+        let mut env = TicTacToeEnv::new(spec, Box::new(RandomOpponent));
+        let path = std::env::temp_dir().join("learn_game_dqn_metrics_test.jsonl");
+        let config = TrainingConfig {
+            num_episodes: 1_500,
+            seed: Some(5),
+            metrics_every: Some(500),
+            metrics_path: path.clone(),
+            epsilon: Schedule::Linear {
+                start: 1.0,
+                end: 0.1,
+                episodes: 1_000,
+            },
+            ..TrainingConfig::default()
+        };
+        // A smaller network and batch keep the unoptimised test build quick.
+        let dqn = DqnConfig {
+            hidden: 32,
+            learning_rate: 0.05,
+            batch_size: 16,
+            ..DqnConfig::default()
+        };
+        let mut agent = train(&mut env, &config, dqn);
+        let player = agent.player("DQN".to_string());
+        // The Huber loss never exceeds the absolute TD error it is computed from.
+        let stats = agent.learn_episode(&mut env, &config.at(config.num_episodes), &mut rng::seeded(0));
+        assert!(stats.updates > 0 && stats.td_error > 0.0);
+        assert!(stats.loss <= stats.td_error);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let last: serde_json::Value = serde_json::from_str(text.lines().nth(2).unwrap()).unwrap();
+        assert_eq!(last["episode"], 1_500);
+        let (loss, td_error) = (last["mean_loss"].as_f64().unwrap(), last["mean_abs_td_error"].as_f64().unwrap());
+        assert!(loss > 0.0 && loss <= td_error);
+        let random = RandomPlayer::new("Random".to_string());
+        let options = EvalOptions {
+            spec,
+            seed: Some(1),
+            ..EvalOptions::default()
+        };
+        let report = evaluate::evaluate(
+            Box::new(player),
+            &mut QTable::new(),
+            Box::new(random),
+            &mut QTable::new(),
+            200,
+            &options,
+        );
+        // Random play wins well under half of these games.
+        assert!(report.win_rate.estimate > 0.6, "{report}");
+        assert!(report.loss_rate.estimate < 0.3, "{report}");
+    }
+}
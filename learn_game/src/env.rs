@@ -0,0 +1,279 @@
+use crate::board::{Board, BoardSpec, GameState, IsGameOver};
+use crate::players::{Marks, Player, RandomPlayer};
+use crate::q_table::QTable;
+use crate::rng::{self, GameRng};
+use rand::Rng;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub state: GameState,
+    pub agent_mark: Marks,
+    pub to_move: Marks,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rewards {
+    pub win: f32,
+    pub draw: f32,
+    pub loss: f32,
+    pub step: f32,
+    pub illegal: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StepInfo {
+    pub winner: Option<Marks>,
+    pub illegal_action: bool,
+    pub opponent_action: Option<usize>,
+}
+
+pub trait OpponentPolicy: fmt::Debug {
+    fn act(&mut self, board: &Board, mark: &Marks, rng: &mut GameRng) -> (usize, usize);
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomOpponent;
+
+pub struct PlayerOpponent {
+    pub player: Box<dyn Player>,
+    pub q: QTable,
+}
+
+#[derive(Debug)]
+pub struct TicTacToeEnv {
+    pub spec: BoardSpec,
+    pub rewards: Rewards,
+    pub agent_mark: Option<Marks>,
+    board: Board,
+    agent: Marks,
+    opponent: Box<dyn OpponentPolicy>,
+    rng: GameRng,
+    done: bool,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            win: 1.0,
+            draw: 0.0,
+            loss: -1.0,
+            step: 0.0,
+            illegal: -1.0,
+        }
+    }
+}
+
+impl Observation {
+    pub fn encode(&self) -> Vec<f32> {
+        let (mine, theirs) = (self.agent_mark.as_char(), self.agent_mark.other().as_char());
+        self.state
+            .iter()
+            .map(|&c| {
+                if c == mine {
+                    1.0
+                } else if c == theirs {
+                    -1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+}
+
+impl OpponentPolicy for RandomOpponent {
+    fn act(&mut self, board: &Board, _mark: &Marks, rng: &mut GameRng) -> (usize, usize) {
+        RandomPlayer::random_move(board, rng)
+    }
+}
+
+impl fmt::Debug for PlayerOpponent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PlayerOpponent")
+            .field("player", &self.player.get_name())
+            .field("q", &self.q.len())
+            .finish()
+    }
+}
+
+impl PlayerOpponent {
+    pub fn new(player: Box<dyn Player>, q: QTable) -> Self {
+        PlayerOpponent { player, q }
+    }
+}
+
+impl OpponentPolicy for PlayerOpponent {
+    fn act(&mut self, board: &Board, mark: &Marks, rng: &mut GameRng) -> (usize, usize) {
+        if self.player.get_mark() != mark {
+            self.player.set_mark(*mark);
+        }
+        self.player.choose_move(board, &mut self.q, rng)
+    }
+}
+
+impl TicTacToeEnv {
+    pub fn new(spec: BoardSpec, opponent: Box<dyn OpponentPolicy>) -> Self {
+        TicTacToeEnv {
+            spec,
+            rewards: Rewards::default(),
+            agent_mark: None,
+            board: Board::with_spec(spec),
+            agent: Marks::CROSS,
+            opponent,
+            rng: rng::from_entropy(),
+            done: true,
+        }
+    }
+    pub fn with_rewards(mut self, rewards: Rewards) -> Self {
+        self.rewards = rewards;
+        self
+    }
+    pub fn with_agent_mark(mut self, mark: Marks) -> Self {
+        self.agent_mark = Some(mark);
+        self
+    }
+    pub fn action_to_move(&self, action: usize) -> (usize, usize) {
+        (action / self.spec.cols, action % self.spec.cols)
+    }
+    pub fn move_to_action(&self, mv: &(usize, usize)) -> usize {
+        mv.0 * self.spec.cols + mv.1
+    }
+    pub fn state(&self) -> &GameState {
+        &self.board.current_state
+    }
+    pub fn observation(&self) -> Observation {
+        let state = self.board.current_state.clone();
+        let to_move = state.side_to_move();
+        Observation {
+            state,
+            agent_mark: self.agent,
+            to_move,
+        }
+    }
+    pub fn legal_action_mask(&self) -> Vec<bool> {
+        if self.done {
+            return vec![false; self.spec.cells()];
+        }
+        self.board.current_state.iter().map(|&c| c == '-').collect()
+    }
+
+    pub fn reset(&mut self, seed: Option<u64>) -> Observation {
+        if let Some(seed) = seed {
+            self.rng = rng::seeded(seed);
+        }
+        self.board = Board::with_spec(self.spec);
+        self.done = false;
+        self.agent = match self.agent_mark {
+            Some(mark) => mark,
+            None if self.rng.gen_bool(0.5) => Marks::CROSS,
+            None => Marks::NOUGHT,
+        };
+        if self.agent == Marks::NOUGHT {
+            let mv = self.opponent.act(&self.board, &Marks::CROSS, &mut self.rng);
+            self.apply(&mv, &Marks::CROSS);
+        }
+        self.observation()
+    }
+
+    fn apply(&mut self, mv: &(usize, usize), mark: &Marks) -> IsGameOver {
+        self.board.next_state[[mv.0, mv.1]] = mark.as_char();
+        let is_over = self.board.is_game_over(mark);
+        self.board.previous_state = self.board.current_state.clone();
+        self.board.current_state = self.board.next_state.clone();
+        is_over
+    }
+
+    pub fn step(&mut self, action: usize) -> (Observation, f32, bool, StepInfo) {
+        let mut info = StepInfo::default();
+        if self.done || action >= self.spec.cells() || !self.legal_action_mask()[action] {
+            self.done = true;
+            info.illegal_action = true;
+            return (self.observation(), self.rewards.illegal, true, info);
+        }
+        let mv = self.action_to_move(action);
+        let agent = self.agent;
+        let reward = match self.apply(&mv, &agent) {
+            IsGameOver::Win => {
+                info.winner = Some(agent);
+                Some(self.rewards.win)
+            }
+            IsGameOver::Drawn => Some(self.rewards.draw),
+            IsGameOver::InPlay => {
+                let opponent = agent.other();
+                let reply = self.opponent.act(&self.board, &opponent, &mut self.rng);
+                info.opponent_action = Some(self.move_to_action(&reply));
+                match self.apply(&reply, &opponent) {
+                    IsGameOver::Win => {
+                        info.winner = Some(opponent);
+                        Some(self.rewards.loss)
+                    }
+                    IsGameOver::Drawn => Some(self.rewards.draw),
+                    IsGameOver::InPlay => None,
+                }
+            }
+        };
+        self.done = reward.is_some();
+        (
+            self.observation(),
+            reward.unwrap_or(self.rewards.step),
+            self.done,
+            info,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::players::MinimaxPlayer;
+
+    #[test]
+    fn is_env_working() {
+        let mut env = TicTacToeEnv::new(BoardSpec::default(), Box::new(RandomOpponent));
+        let first = env.reset(Some(7));
+        let again = env.reset(Some(7));
+        assert_eq!(first.state.to_state_key(), again.state.to_state_key());
+        assert_eq!(first.agent_mark, again.agent_mark);
+        assert_eq!(first.to_move, first.agent_mark);
+        let mut done = false;
+        while !done {
+            let mask = env.legal_action_mask();
+            let action = mask.iter().position(|&legal| legal).unwrap();
+            let (obs, reward, finished, info) = env.step(action);
+            assert!(!info.illegal_action);
+            assert_eq!(obs.encode().len(), 9);
+            if finished {
+                match info.winner {
+                    Some(mark) if mark == obs.agent_mark => assert_eq!(reward, 1.0),
+                    Some(_) => assert_eq!(reward, -1.0),
+                    None => assert_eq!(reward, 0.0),
+                }
+            }
+            done = finished;
+        }
+        assert!(env.legal_action_mask().iter().all(|&legal| !legal));
+        let (_, reward, done, info) = env.step(0);
+        assert!(done && info.illegal_action);
+        assert_eq!(reward, -1.0);
+    }
+
+    #[test]
+    fn is_env_with_player_opponent_working() {
+        let minimax = MinimaxPlayer::perfect("Minimax".to_string(), BoardSpec::default());
+        let opponent = PlayerOpponent::new(Box::new(minimax), QTable::new());
+        let mut env = TicTacToeEnv::new(BoardSpec::default(), Box::new(opponent))
+            .with_agent_mark(Marks::NOUGHT);
+        for seed in 0..5 {
+            let obs = env.reset(Some(seed));
+            assert_eq!(obs.state.available_moves().len(), 8);
+            let mut done = false;
+            let mut reward = 0.0;
+            while !done {
+                let action = env.legal_action_mask().iter().position(|&legal| legal).unwrap();
+                (_, reward, done, _) = env.step(action);
+            }
+            assert!(reward <= 0.0);
+        }
+    }
+}
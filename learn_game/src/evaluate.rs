@@ -0,0 +1,249 @@
+use crate::board::{Board, BoardSpec};
+use crate::config::{Hyperparameters, TrainingConfig};
+use crate::players::{Marks, MinimaxPlayer, Player};
+use crate::q_table::QTable;
+use crate::Game;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalOptions {
+    pub spec: BoardSpec,
+    pub seed: Option<u64>,
+    pub greedy: bool,
+    pub z: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Record {
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Interval {
+    pub estimate: f32,
+    pub lower: f32,
+    pub upper: f32,
+}
+
+// All records and rates are from agent A's point of view; B's are the mirror image.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvalReport {
+    pub agent_a: String,
+    pub agent_b: String,
+    pub as_cross: Record,
+    pub as_nought: Record,
+    pub avg_length: f32,
+    pub win_rate: Interval,
+    pub draw_rate: Interval,
+    pub loss_rate: Interval,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        EvalOptions {
+            spec: BoardSpec::default(),
+            seed: None,
+            greedy: true,
+            z: 1.96,
+        }
+    }
+}
+
+impl Record {
+    pub fn add(&mut self, outcome: Option<bool>) {
+        self.games += 1;
+        match outcome {
+            Some(true) => self.wins += 1,
+            Some(false) => self.losses += 1,
+            None => self.draws += 1,
+        }
+    }
+    pub fn merge(&self, other: &Record) -> Record {
+        Record {
+            games: self.games + other.games,
+            wins: self.wins + other.wins,
+            draws: self.draws + other.draws,
+            losses: self.losses + other.losses,
+        }
+    }
+}
+
+impl Interval {
+    pub fn wilson(successes: usize, n: usize, z: f32) -> Self {
+        if n == 0 {
+            return Interval {
+                estimate: 0.0,
+                lower: 0.0,
+                upper: 1.0,
+            };
+        }
+        let (n, p) = (n as f32, successes as f32 / n as f32);
+        let denominator = 1.0 + z * z / n;
+        let center = (p + z * z / (2.0 * n)) / denominator;
+        let half = z / denominator * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt();
+        Interval {
+            estimate: p,
+            lower: (center - half).max(0.0),
+            upper: (center + half).min(1.0),
+        }
+    }
+}
+
+impl EvalReport {
+    pub fn total(&self) -> Record {
+        self.as_cross.merge(&self.as_nought)
+    }
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.total();
+        write!(
+            f,
+            "{} vs {}: {} games, W/D/L {}/{}/{} (as X {}/{}/{}, as 0 {}/{}/{}), win {:.3} [{:.3}, {:.3}], avg length {:.2}",
+            self.agent_a,
+            self.agent_b,
+            total.games,
+            total.wins,
+            total.draws,
+            total.losses,
+            self.as_cross.wins,
+            self.as_cross.draws,
+            self.as_cross.losses,
+            self.as_nought.wins,
+            self.as_nought.draws,
+            self.as_nought.losses,
+            self.win_rate.estimate,
+            self.win_rate.lower,
+            self.win_rate.upper,
+            self.avg_length,
+        )
+    }
+}
+
+// A perfect opponent where the board can be solved outright, a depth-limited search otherwise.
+pub fn reference_opponent(spec: BoardSpec) -> MinimaxPlayer {
+    if spec.cells() <= 9 {
+        MinimaxPlayer::perfect("minimax".to_string(), spec)
+    } else {
+        MinimaxPlayer::new("minimax".to_string(), QTable::new()).with_max_depth(4)
+    }
+}
+
+// Each agent plays from its own table, so two learned agents can be compared; agents without one ignore theirs.
+pub fn evaluate(
+    mut agent_a: Box<dyn Player>,
+    q_a: &mut QTable,
+    mut agent_b: Box<dyn Player>,
+    q_b: &mut QTable,
+    n_games: usize,
+    options: &EvalOptions,
+) -> EvalReport {
+    if options.greedy {
+        agent_a.set_hyperparameters(&Hyperparameters::greedy());
+        agent_b.set_hyperparameters(&Hyperparameters::greedy());
+    }
+    let (name_a, name_b) = (agent_a.get_name().to_owned(), agent_b.get_name().to_owned());
+    let a_seat: *const dyn Player = &*agent_a;
+    let config = TrainingConfig {
+        seed: options.seed,
+        ..TrainingConfig::default()
+    };
+    let mut game = Game::with_spec(agent_a, agent_b, options.spec).with_config(config);
+    // The agents may share a name, so A is found by where its box was seated.
+    let mut a_is_current = std::ptr::addr_eq(&*game.current_player, a_seat);
+    let (mut as_cross, mut as_nought) = (Record::default(), Record::default());
+    let mut total_moves = 0;
+    for i in 0..n_games {
+        let a_mark = if i % 2 == 0 { Marks::CROSS } else { Marks::NOUGHT };
+        if a_is_current != (a_mark == Marks::CROSS) {
+            game.swap_players();
+        }
+        game.current_player.set_mark(Marks::CROSS);
+        game.other_player.set_mark(Marks::NOUGHT);
+        game.board = Board::with_spec(options.spec);
+        // Cross moves first, so A is the current player exactly when it plays cross.
+        let result = if a_mark == Marks::CROSS {
+            game.play_turns(q_a, Some(q_b))
+        } else {
+            game.play_turns(q_b, Some(q_a))
+        };
+        total_moves += result.moves.len();
+        // play_turns leaves the last mover as the current player.
+        a_is_current = (a_mark == Marks::CROSS) == (result.moves.len() % 2 == 1);
+        let outcome = result.winner.map(|winner| winner == a_mark);
+        match a_mark {
+            Marks::CROSS => as_cross.add(outcome),
+            _ => as_nought.add(outcome),
+        }
+    }
+    let total = as_cross.merge(&as_nought);
+    EvalReport {
+        agent_a: name_a,
+        agent_b: name_b,
+        as_cross,
+        as_nought,
+        avg_length: total_moves as f32 / n_games.max(1) as f32,
+        win_rate: Interval::wilson(total.wins, total.games, options.z),
+        draw_rate: Interval::wilson(total.draws, total.games, options.z),
+        loss_rate: Interval::wilson(total.losses, total.games, options.z),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::players::ComputerPlayerRLmax;
+    use crate::solver;
+
+    #[test]
+    fn is_wilson_interval_working() {
+        let interval = Interval::wilson(50, 100, 1.96);
+        assert_eq!(interval.estimate, 0.5);
+        assert!((interval.lower - 0.4038).abs() < 1e-3 && (interval.upper - 0.5962).abs() < 1e-3);
+        let zero = Interval::wilson(0, 20, 1.96);
+        assert_eq!(zero.lower, 0.0);
+        assert!(zero.upper > 0.1 && zero.upper < 0.2);
+    }
+
+    #[test]
+    fn is_evaluate_working() {
+        let options = EvalOptions {
+            seed: Some(3),
+            ..EvalOptions::default()
+        };
+        let perfect = || Box::new(MinimaxPlayer::perfect("Minimax".to_string(), options.spec));
+        let report = evaluate(perfect(), &mut QTable::new(), perfect(), &mut QTable::new(), 10, &options);
+        assert_eq!(report.total().draws, 10);
+        assert_eq!((report.as_cross.games, report.as_nought.games), (5, 5));
+        assert_eq!(report.avg_length, 9.0);
+
+        let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+        let report = evaluate(rl_max, &mut QTable::new(), perfect(), &mut QTable::new(), 40, &options);
+        assert_eq!(report.total().wins, 0);
+        assert!(report.loss_rate.estimate > 0.5);
+        assert!(report.loss_rate.lower <= report.loss_rate.estimate);
+        assert_eq!(report.as_cross.merge(&report.as_nought).games, 40);
+        println!("{report}");
+    }
+
+    #[test]
+    fn is_evaluate_with_two_tables_working() {
+        let options = EvalOptions {
+            seed: Some(5),
+            ..EvalOptions::default()
+        };
+        let mut perfect = solver::solve(options.spec).to_q_table();
+        // Both sides look their moves up as "max"; only their tables tell them apart.
+        let player = || Box::new(ComputerPlayerRLmax::new("max".to_string()));
+        let report = evaluate(player(), &mut perfect, player(), &mut QTable::new(), 100, &options);
+        assert_eq!(report.total().losses, 0, "{report}");
+        assert!(report.total().wins > 50, "{report}");
+        let report = evaluate(player(), &mut QTable::new(), player(), &mut perfect, 100, &options);
+        assert_eq!(report.total().wins, 0, "{report}");
+    }
+}
@@ -0,0 +1,266 @@
+use crate::board::BoardSpec;
+use crate::config::TrainingConfig;
+use crate::evaluate::{self, Interval, Record};
+use crate::players::{ComputerPlayerRLmax, HeuristicPlayer, Player, RandomPlayer, SnapshotPlayer};
+use crate::q_table::QTable;
+use crate::rng::GameRng;
+use crate::Game;
+use rand::prelude::SliceRandom;
+use std::fmt;
+use std::mem;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LeagueConfig {
+    // Episodes between frozen copies of the learner's table joining the pool.
+    pub snapshot_every: usize,
+    // The oldest snapshot leaves once the pool holds this many.
+    pub max_snapshots: usize,
+    pub weights: OpponentWeights,
+}
+
+// Relative chance of meeting each kind of opponent; the snapshots share theirs equally.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpponentWeights {
+    pub snapshots: f32,
+    pub random: f32,
+    pub minimax: f32,
+    pub heuristic: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpponentKind {
+    Snapshot,
+    Random,
+    Minimax,
+    Heuristic,
+}
+
+// Every opponent sits at the table as "RLmin", so its positions share the learner's minimizing entries.
+pub struct Opponent {
+    pub name: String,
+    pub kind: OpponentKind,
+    pub player: Box<dyn Player>,
+    // The learner's results against this opponent while training.
+    pub record: Record,
+}
+
+pub struct League {
+    pub spec: BoardSpec,
+    pub config: LeagueConfig,
+    pub opponents: Vec<Opponent>,
+}
+
+impl Default for LeagueConfig {
+    fn default() -> Self {
+        LeagueConfig {
+            snapshot_every: 10_000,
+            max_snapshots: 5,
+            weights: OpponentWeights::default(),
+        }
+    }
+}
+
+impl Default for OpponentWeights {
+    fn default() -> Self {
+        OpponentWeights {
+            snapshots: 0.4,
+            random: 0.2,
+            minimax: 0.2,
+            heuristic: 0.2,
+        }
+    }
+}
+
+impl LeagueConfig {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let weights = self.weights;
+        let all = [weights.snapshots, weights.random, weights.minimax, weights.heuristic];
+        if all.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err(anyhow::anyhow!("The league weights should be finite and not negative."));
+        }
+        if self.snapshot_every == 0 {
+            return Err(anyhow::anyhow!("The league needs `snapshot_every` above 0."));
+        }
+        if weights.snapshots > 0.0 && self.max_snapshots == 0 {
+            return Err(anyhow::anyhow!("Snapshots have a weight but `max_snapshots` is 0."));
+        }
+        if all.iter().sum::<f32>() <= 0.0 {
+            return Err(anyhow::anyhow!("At least one kind of opponent needs a positive weight."));
+        }
+        Ok(())
+    }
+}
+
+impl Opponent {
+    pub fn new(name: &str, kind: OpponentKind, player: Box<dyn Player>) -> Self {
+        Opponent {
+            name: name.to_string(),
+            kind,
+            player,
+            record: Record::default(),
+        }
+    }
+    pub fn win_rate(&self) -> Interval {
+        Interval::wilson(self.record.wins, self.record.games, 1.96)
+    }
+}
+
+impl League {
+    pub fn new(spec: BoardSpec, config: LeagueConfig) -> Result<Self, anyhow::Error> {
+        config.validate()?;
+        let mut minimax = evaluate::reference_opponent(spec);
+        minimax.name = "RLmin".to_string();
+        let opponents = vec![
+            Opponent::new("random", OpponentKind::Random, Box::new(RandomPlayer::new("RLmin".to_string()))),
+            Opponent::new("minimax", OpponentKind::Minimax, Box::new(minimax)),
+            Opponent::new(
+                "heuristic",
+                OpponentKind::Heuristic,
+                Box::new(HeuristicPlayer::new("RLmin".to_string())),
+            ),
+        ];
+        Ok(League {
+            spec,
+            config,
+            opponents,
+        })
+    }
+    pub fn snapshots(&self) -> usize {
+        self.opponents.iter().filter(|o| o.kind == OpponentKind::Snapshot).count()
+    }
+    pub fn snapshot(&mut self, q: &QTable, episode: usize) {
+        if self.snapshots() == self.config.max_snapshots {
+            let oldest = self.opponents.iter().position(|o| o.kind == OpponentKind::Snapshot).unwrap();
+            self.opponents.remove(oldest);
+        }
+        let player = SnapshotPlayer::new("RLmin".to_string(), q.clone());
+        let name = format!("snapshot@{episode}");
+        self.opponents.push(Opponent::new(&name, OpponentKind::Snapshot, Box::new(player)));
+    }
+    fn weight(&self, kind: OpponentKind) -> f32 {
+        let weights = self.config.weights;
+        match kind {
+            OpponentKind::Snapshot => weights.snapshots / self.snapshots() as f32,
+            OpponentKind::Random => weights.random,
+            OpponentKind::Minimax => weights.minimax,
+            OpponentKind::Heuristic => weights.heuristic,
+        }
+    }
+    pub fn sample(&self, rng: &mut GameRng) -> usize {
+        let indices: Vec<usize> = (0..self.opponents.len()).collect();
+        *indices
+            .choose_weighted(rng, |&i| self.weight(self.opponents[i].kind))
+            .expect("At least one opponent should have a positive weight.")
+    }
+    // RLmax learns against an opponent drawn from the pool each episode, with a frozen copy of its table joining every so often.
+    // The first copy, of the still untrained table, joins before the first episode so a snapshot-only pool is never empty.
+    pub fn train(&mut self, config: TrainingConfig) -> QTable {
+        let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+        // Holds the opponent's seat between episodes; each episode swaps a pool member in.
+        let seat = Box::new(RandomPlayer::new("RLmin".to_string()));
+        let mut game = Game::with_spec(rl_max, seat, self.spec).with_config(config);
+        let mut q = QTable::with_symmetry();
+        while game.episode < game.config.num_episodes {
+            if game.episode.is_multiple_of(self.config.snapshot_every) {
+                self.snapshot(&q, game.episode);
+            }
+            let i = self.sample(&mut game.rng);
+            mem::swap(opponent_seat(&mut game), &mut self.opponents[i].player);
+            let stats = game.learn_episode(Some(&mut q));
+            mem::swap(opponent_seat(&mut game), &mut self.opponents[i].player);
+            self.opponents[i].record.add(stats.winner_name.map(|name| name == "RLmax"));
+        }
+        q
+    }
+}
+
+fn opponent_seat(game: &mut Game) -> &mut Box<dyn Player> {
+    if game.current_player.get_name() == "RLmax" {
+        &mut game.other_player
+    } else {
+        &mut game.current_player
+    }
+}
+
+impl fmt::Display for League {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for opponent in &self.opponents {
+            let (record, rate) = (opponent.record, opponent.win_rate());
+            writeln!(
+                f,
+                "vs {}: {} games, W/D/L {}/{}/{}, win {:.3} [{:.3}, {:.3}]",
+                opponent.name, record.games, record.wins, record.draws, record.losses, rate.estimate, rate.lower, rate.upper,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_league_working() {
+        let config = LeagueConfig {
+            snapshot_every: 500,
+            max_snapshots: 2,
+            ..LeagueConfig::default()
+        };
+        let mut league = League::new(BoardSpec::default(), config).unwrap();
+        let training = TrainingConfig {
+            num_episodes: 3_000,
+            seed: Some(4),
+            ..TrainingConfig::default()
+        };
+        league.train(training);
+        // Snapshots taken at 0 to 2_500, of which only the last two remain.
+        let names: Vec<&str> = league.opponents.iter().skip(3).map(|o| o.name.as_str()).collect();
+        assert_eq!(names, vec!["snapshot@2000", "snapshot@2500"]);
+        assert!(league.opponents.iter().all(|o| o.record.games > 0), "{league}");
+        // Perfect play never loses, while random play gives up plenty of wins.
+        assert_eq!(league.opponents[1].record.wins, 0, "{league}");
+        assert!(league.opponents[0].win_rate().estimate > league.opponents[1].win_rate().estimate, "{league}");
+    }
+
+    #[test]
+    fn is_league_config_validated() {
+        let only = |weights| LeagueConfig {
+            weights,
+            ..LeagueConfig::default()
+        };
+        let none = OpponentWeights {
+            snapshots: 0.0,
+            random: 0.0,
+            minimax: 0.0,
+            heuristic: 0.0,
+        };
+        let snapshots = OpponentWeights {
+            snapshots: 1.0,
+            ..none
+        };
+        // Snapshots alone are enough: the untrained table is the first one in the pool.
+        let mut league = League::new(BoardSpec::default(), only(snapshots)).unwrap();
+        league.train(TrainingConfig {
+            num_episodes: 20,
+            seed: Some(1),
+            ..TrainingConfig::default()
+        });
+        assert_eq!(league.opponents[3].name, "snapshot@0");
+        assert_eq!(league.opponents[3].record.games, 20);
+
+        assert!(League::new(BoardSpec::default(), only(none)).is_err());
+        let negative = OpponentWeights { random: -1.0, ..snapshots };
+        assert!(League::new(BoardSpec::default(), only(negative)).is_err());
+        let no_room = LeagueConfig {
+            max_snapshots: 0,
+            ..only(snapshots)
+        };
+        assert!(League::new(BoardSpec::default(), no_room).is_err());
+        let never = LeagueConfig {
+            snapshot_every: 0,
+            ..LeagueConfig::default()
+        };
+        assert!(League::new(BoardSpec::default(), never).is_err());
+    }
+}
@@ -1,67 +1,154 @@
-use crate::board::{Board, IsGameOver};
-use crate::config::{NUM_EPISODES, EXPLORATION_RATE, K};
-use crate::players::{ComputerPlayerRLmax, ComputerPlayerRLmin, HumanPlayer, MinimaxPlayer, Marks, Player};
-use crate::q_table::{Moves, QTable};
-use anyhow::Error;
-use ndarray::prelude::*;
+use crate::board::{Board, BoardSpec, GameState, IsGameOver};
+use crate::checkpoint::Checkpoint;
+use crate::evaluate::{EvalOptions, EvalReport};
+use crate::metrics::{EpisodeStats, MetricsLog};
+use crate::config::{Algorithm, Exploration, Hyperparameters, TrainingConfig};
+use crate::double_q::DoubleQTable;
+use crate::league::{League, LeagueConfig};
+use crate::players::{
+    ComputerPlayerRLmax, ComputerPlayerRLmin, HumanPlayer, Marks, MinimaxPlayer, Player,
+};
+use crate::monte_carlo::Trajectory;
+use crate::q_table::{Outcome, QTable, StateKey, Transition};
+use crate::rng::GameRng;
+use crate::traces::EligibilityTraces;
 use rand::prelude::SliceRandom;
-use rand::{thread_rng, Rng};
-use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
-use serde::ser::{SerializeMap, SerializeSeq, Serializer};
-use serde::{Deserialize, Serialize};
-use std::cell::{Cell, RefCell};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::BufReader;
-use std::iter::Iterator;
-use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
-use std::path::{Path, PathBuf};
-use std::thread;
-use std::{fmt, io, mem};
+use std::mem;
+use std::path::Path;
 
+pub mod afterstate;
+pub mod alphazero;
+pub mod bitboard;
 pub mod board;
+pub mod checkpoint;
 pub mod config;
+pub mod double_q;
+pub mod dqn;
+pub mod env;
+pub mod evaluate;
+pub mod league;
+pub mod linear;
+pub mod mcts;
+pub mod metrics;
+pub mod mlp;
+pub mod monte_carlo;
 pub mod players;
 pub mod q_table;
+pub mod reinforce;
+pub mod rng;
+pub mod search;
+pub mod solver;
+pub mod symmetry;
+pub mod traces;
 
 pub struct Game {
     pub board: Board,
     pub current_player: Box<dyn Player>,
     pub other_player: Box<dyn Player>,
+    pub spec: BoardSpec,
+    pub config: TrainingConfig,
 
     episode: usize,
+    pub evaluations: Vec<(usize, EvalReport)>,
+    pub double_q: Option<DoubleQTable>,
+    rng: GameRng,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameResult {
+    pub winner: Option<Marks>,
+    pub winner_name: Option<String>,
+    pub moves: Vec<(Marks, (usize, usize))>,
+    pub final_state: GameState,
+}
+
+impl GameResult {
+    pub fn is_draw(&self) -> bool {
+        self.winner.is_none()
+    }
 }
 
 impl Game {
-    pub fn new(mut player1: Box<dyn Player>, mut player2: Box<dyn Player>) -> Self {
-        if player1.get_name() == "RLmax".to_owned() {
-            let mut rng = rand::thread_rng();
+    pub fn new(player1: Box<dyn Player>, player2: Box<dyn Player>) -> Self {
+        Self::with_spec(player1, player2, BoardSpec::default())
+    }
+    pub fn with_spec(player1: Box<dyn Player>, player2: Box<dyn Player>, spec: BoardSpec) -> Self {
+        Self::with_rng(player1, player2, spec, rng::from_entropy())
+    }
+    pub fn with_seed(player1: Box<dyn Player>, player2: Box<dyn Player>, spec: BoardSpec, seed: u64) -> Self {
+        Self::with_rng(player1, player2, spec, rng::seeded(seed))
+    }
+    pub fn with_rng(
+        mut player1: Box<dyn Player>,
+        mut player2: Box<dyn Player>,
+        spec: BoardSpec,
+        mut rng: GameRng,
+    ) -> Self {
+        if player1.get_name() == "RLmax" {
             player1.set_mark(*[Marks::CROSS, Marks::NOUGHT].choose(&mut rng).unwrap());
         }
         player2.set_mark(player1.get_mark().other());
         if *player1.get_mark() == Marks::CROSS {
             Game {
-                board: Board::new(),
+                board: Board::with_spec(spec),
                 current_player: player1,
                 other_player: player2,
+                spec,
+                config: TrainingConfig::default(),
                 episode: 0,
+                evaluations: Vec::new(),
+                double_q: None,
+                rng,
             }
         } else {
             Game {
-                board: Board::new(),
+                board: Board::with_spec(spec),
                 current_player: player2,
                 other_player: player1,
+                spec,
+                config: TrainingConfig::default(),
                 episode: 0,
+                evaluations: Vec::new(),
+                double_q: None,
+                rng,
             }
         }
     }
-    pub fn assign_players (&mut self) {
-        let mut rng = rand::thread_rng();
-        self.current_player.set_mark(*[Marks::CROSS, Marks::NOUGHT].choose(&mut rng).unwrap());
+    pub fn with_config(mut self, config: TrainingConfig) -> Self {
+        if let Some(seed) = config.seed {
+            self.rng = rng::seeded(seed);
+        }
+        self.config = config;
+        self
+    }
+    pub fn resume(player1: Box<dyn Player>, player2: Box<dyn Player>, checkpoint: Checkpoint) -> (Self, QTable) {
+        let mut game = Self::with_spec(player1, player2, checkpoint.spec);
+        game.config = checkpoint.config;
+        game.episode = checkpoint.episode;
+        game.rng = checkpoint.rng;
+        game.double_q = checkpoint.double_q;
+        (game, checkpoint.q)
+    }
+    pub fn checkpoint(&self, q: &QTable) -> Checkpoint {
+        Checkpoint {
+            episode: self.episode,
+            spec: self.spec,
+            config: self.config.clone(),
+            rng: self.rng.clone(),
+            q: q.clone(),
+            double_q: self.double_q.clone(),
+        }
+    }
+    pub fn assign_players(&mut self) {
+        // Draw the marks in name order so a seeded run does not depend on who moved last.
+        if self.current_player.get_name() > self.other_player.get_name() {
+            self.swap_players();
+        }
+        self.current_player.set_mark(*[Marks::CROSS, Marks::NOUGHT].choose(&mut self.rng).unwrap());
         self.other_player.set_mark(self.current_player.get_mark().other());
-        if *self.current_player.get_mark() == Marks::NOUGHT {self.swap_players();}
+        if *self.current_player.get_mark() == Marks::NOUGHT {
+            self.swap_players();
+        }
     }
     pub fn swap_players(&mut self) {
         mem::swap(&mut self.current_player, &mut self.other_player);
@@ -73,218 +160,260 @@ impl Game {
         );
         self.board.current_state = self.board.next_state.clone();
     }
-    fn learn_episode(&mut self, q: Option<&mut QTable>) {
-        let q = q.expect("QTable is initialized and should be valid at this point.");
-        self.board = Board::new();
+    pub fn play_to_end(&mut self, q: &mut QTable) -> GameResult {
+        self.play_out(q, None)
+    }
+    // Each player consults its own table: `q_current` is the current player's, `q_other` the other's.
+    pub fn play_to_end_with(&mut self, q_current: &mut QTable, q_other: &mut QTable) -> GameResult {
+        self.play_out(q_current, Some(q_other))
+    }
+    // Starts a fresh game on a cleared board with newly drawn marks; each table stays with its player.
+    fn play_out<'a>(&mut self, mut q: &'a mut QTable, mut q_other: Option<&'a mut QTable>) -> GameResult {
+        let current = self.current_player.get_name().to_owned();
+        self.board = Board::with_spec(self.spec);
         self.assign_players();
+        if self.current_player.get_name() != current {
+            if let Some(other) = q_other.as_mut() {
+                mem::swap(&mut q, other);
+            }
+        }
+        self.play_turns(q, q_other)
+    }
+    // Plays on from the board and the marks as they stand, for callers that set them up themselves.
+    fn play_turns<'a>(&mut self, mut q: &'a mut QTable, mut q_other: Option<&'a mut QTable>) -> GameResult {
+        let mut moves = Vec::new();
         loop {
-            let current_state_key = self.board.current_state.to_state_key() + self.current_player.get_name();
-            q.entry(current_state_key.clone())
-                .or_insert(Moves::new(self.board.current_state.available_moves()));
-            let mv = if self.episode > 200_000 {
-                self.current_player.choose_move_k(&self.board, q)
-                } else {
-                    self.current_player.choose_move(&self.board, q)
-                    };
+            let mv = self.current_player.choose_move(&self.board, q, &mut self.rng);
             self.current_player.make_move(&mut self.board, &mv);
-            let next_state_key = self.board.next_state.to_state_key() + self.other_player.get_name();
-            q.entry(next_state_key.clone())
-                .or_insert(Moves::new(self.board.next_state.available_moves()));
-            let is_over = self.board.is_game_over(&self.current_player.get_mark());
-            match is_over {
+            moves.push((*self.current_player.get_mark(), mv));
+            match self.board.is_game_over(self.current_player.get_mark()) {
                 IsGameOver::InPlay => {
-                    q.update_q_table(
-                        &current_state_key,
-                        &next_state_key,
-                        &mv,
-                        self.current_player.get_name(),
-                        0.0,
-                        false,
-                    );
+                    if let Some(other) = q_other.as_mut() {
+                        mem::swap(&mut q, other);
+                    }
                     self.swap_players();
                     self.swap_states();
                 }
                 IsGameOver::Drawn => {
-                    q.update_q_table(
-                        &current_state_key,
-                        &next_state_key,
-                        &mv,
-                        self.current_player.get_name(),
-                        0.0,
-                        true,
-                    );
-                    self.episode += 1;
-                    break;
+                    return GameResult {
+                        winner: None,
+                        winner_name: None,
+                        moves,
+                        final_state: self.board.next_state.clone(),
+                    };
                 }
                 IsGameOver::Win => {
-                    let mut reward = 0.0;
-                    if self.current_player.get_name() == "RLmax" {
-                        reward = 1.0;
-                    } else {
-                        reward = -1.0;
-                    }
-                    q.update_q_table(
-                        &current_state_key,
-                        &next_state_key,
-                        &mv,
-                        self.current_player.get_name(),
-                        reward,
-                        true,
-                    );
-                    self.episode += 1;
-                    break;
+                    return GameResult {
+                        winner: Some(*self.current_player.get_mark()),
+                        winner_name: Some(self.current_player.get_name().to_owned()),
+                        moves,
+                        final_state: self.board.next_state.clone(),
+                    };
                 }
             }
         }
     }
-    fn learn_episode_with_minimax(&mut self, q: Option<&mut QTable>) {
+    // Creates the entry for the current or the next position, optimistically when that is how the run explores.
+    // The current position's entry is made before the move, while `next_state` still holds it.
+    fn entry_for(&mut self, q: &mut QTable, key: &StateKey, next: bool, params: &Hyperparameters) {
+        if q.contains_key(&key.key) {
+            return;
+        }
+        let player = if next {
+            self.other_player.get_name()
+        } else {
+            self.current_player.get_name()
+        };
+        let moves = self.board.available_moves();
+        match params.exploration {
+            Exploration::Optimistic { value } => {
+                let value = if player == "RLmin" { -value } else { value };
+                q.optimistic_entry_with(key, moves, value);
+            }
+            _ => {
+                q.entry_with(key, moves, &mut self.rng);
+            }
+        }
+    }
+    pub fn learn_episode(&mut self, q: Option<&mut QTable>) -> EpisodeStats {
         let q = q.expect("QTable is initialized and should be valid at this point.");
-        self.board = Board::new();
+        let params = self.config.at(self.episode);
+        self.current_player.set_hyperparameters(&params);
+        self.other_player.set_hyperparameters(&params);
+        self.board = Board::with_spec(self.spec);
+        if !self.config.bitboard {
+            self.board.bits = None;
+        }
         self.assign_players();
+        if self.config.algorithm == Algorithm::DoubleQLearning && self.double_q.is_none() {
+            self.double_q = Some(DoubleQTable::new(q));
+        }
+        let mut stats = EpisodeStats::default();
+        let mut traces = EligibilityTraces::new(self.config.traces, self.config.lambda);
+        let mut trajectory = Trajectory::new(self.config.visits);
+        // SARSA waits for the reply actually played before updating the previous move.
+        let mut pending: Option<(String, (usize, usize))> = None;
         loop {
-            let current_state_key = self.board.current_state.to_state_key() + self.current_player.get_name();
-            q.entry(current_state_key.clone())
-                .or_insert(Moves::new(self.board.current_state.available_moves()));
-            let mv = if self.episode > 10_000 {
-                self.current_player.choose_move_k(&self.board, q)
-                } else {
-                    self.current_player.choose_move(&self.board, q)
-                    };
-            self.current_player.make_move(&mut self.board, &mv);
-            let next_state_key = self.board.next_state.to_state_key() + self.other_player.get_name();
-            q.entry(next_state_key.clone())
-                .or_insert(Moves::new(self.board.next_state.available_moves()));
-            let is_over = self.board.is_game_over(&self.current_player.get_mark());
-            match is_over {
-                IsGameOver::InPlay => {
-                    q.update_q_table(
-                        &current_state_key,
-                        &next_state_key,
-                        &mv,
-                        self.current_player.get_name(),
-                        0.0,
-                        false,
-                    );
-                    self.swap_players();
-                    self.swap_states();
+            let current_key = q.board_key(&self.board, self.current_player.get_name());
+            self.entry_for(q, &current_key, false, &params);
+            let mv = if self.episode > self.config.softmax_after {
+                let mv = self.current_player.choose_move_k(&self.board, q, &mut self.rng);
+                if let Some(entropy) = self.current_player.last_entropy() {
+                    stats.entropy += entropy;
+                    stats.sampled_moves += 1;
                 }
-                IsGameOver::Drawn => {
-                    q.update_q_table(
-                        &current_state_key,
-                        &next_state_key,
-                        &mv,
-                        self.current_player.get_name(),
-                        0.0,
-                        true,
-                    );
-                    self.episode += 1;
-                    break;
-                }
-                IsGameOver::Win => {
-                    let mut reward = 0.0;
-                    if self.current_player.get_name() == "RLmax" {
-                        reward = 1.0;
-                    } else {
-                        reward = -1.0;
+                mv
+            } else {
+                self.current_player.choose_move(&self.board, q, &mut self.rng)
+            };
+            if let Some((previous_key, previous_move)) = pending.take() {
+                let previous = Transition {
+                    key: &previous_key,
+                    mv: previous_move,
+                    player: self.other_player.get_name(),
+                    outcome: Outcome::InPlay(&current_key.key),
+                };
+                stats.record_update(q.sarsa(&previous, &current_key.to_table(&mv), &params));
+            }
+            self.current_player.make_move(&mut self.board, &mv);
+            self.board.track_move(&mv, self.current_player.get_mark());
+            let next_key = q.board_key(&self.board, self.other_player.get_name());
+            self.entry_for(q, &next_key, true, &params);
+            if let Some(double) = self.double_q.as_mut().filter(|_| self.config.algorithm == Algorithm::DoubleQLearning) {
+                double.track(q, &current_key.key);
+                double.track(q, &next_key.key);
+            }
+            let table_move = current_key.to_table(&mv);
+            q.get_mut(&current_key.key).unwrap().visit(&table_move);
+            let is_over = self.board.is_game_over_after(&mv, self.current_player.get_mark());
+            let outcome = match is_over {
+                IsGameOver::InPlay => Outcome::InPlay(&next_key.key),
+                IsGameOver::Win if self.current_player.get_name() == "RLmax" => Outcome::Over(1.0),
+                IsGameOver::Win => Outcome::Over(-1.0),
+                IsGameOver::Drawn => Outcome::Over(0.0),
+            };
+            let transition = Transition {
+                key: &current_key.key,
+                mv: table_move,
+                player: self.current_player.get_name(),
+                outcome,
+            };
+            match self.config.algorithm {
+                Algorithm::QLearning => stats.record_update(q.q_learning(&transition, &params)),
+                Algorithm::Sarsa => match outcome {
+                    Outcome::InPlay(_) => pending = Some((current_key.key.clone(), table_move)),
+                    // Nothing follows the last move, so there is no reply to wait for.
+                    Outcome::Over(_) => stats.record_update(q.q_learning(&transition, &params)),
+                },
+                Algorithm::ExpectedSarsa => stats.record_update(q.expected_sarsa(&transition, &params)),
+                Algorithm::TdLambda => stats.record_update(traces.learn(q, &transition, &params)),
+                Algorithm::MonteCarlo => {
+                    for td_error in trajectory.learn(q, &transition, &params) {
+                        stats.record_update(td_error);
                     }
-                    q.update_q_table(
-                        &current_state_key,
-                        &next_state_key,
-                        &mv,
-                        self.current_player.get_name(),
-                        reward,
-                        true,
-                    );
-                    self.episode += 1;
-                    break;
                 }
-            }
-        }
-    }
-    fn learn_q_table(&mut self, mut q: Option<&mut QTable>) {
-        loop {
-            if self.episode > 100_000 && self.episode % 10_000 == 0 {
-                if EXPLORATION_RATE.with_borrow(|erate| -> bool {*erate > 0.1}) {
-                    EXPLORATION_RATE.with_borrow_mut(|erate| {
-                        *erate -= 0.1;
-                    });
+                Algorithm::DoubleQLearning => {
+                    let double = self.double_q.as_mut().unwrap();
+                    stats.record_update(double.learn(q, &mut self.rng, &transition, &params));
                 }
             }
-            if self.episode > 100_000 && self.episode % 5_000 == 0 {
-                K.with_borrow_mut(|k| {
-                    *k += 0.05;
-                    println!("K value is {:?} after episode {:?}", k, self.episode);
-                });
+            if is_over == IsGameOver::InPlay {
+                self.swap_players();
+                self.swap_states();
+                continue;
             }
-            if self.episode >= NUM_EPISODES {
-                println!("episode {:?}", self.episode);
-                break;
+            self.episode += 1;
+            if is_over == IsGameOver::Win {
+                stats.winner = Some(*self.current_player.get_mark());
+                stats.winner_name = Some(self.current_player.get_name().to_owned());
             }
-            self.learn_episode(q.as_deref_mut());
+            return stats;
         }
-        let path = Path::new("./q_table_archive/");
-        let is_q_saved = q_table::q_table_to_disk(&path, q.as_deref().unwrap());
     }
-    fn learn_q_table_with_minimax(&mut self, mut q: Option<&mut QTable>) {
-        loop {
-            if self.episode > 5_000 && self.episode % 1_000 == 0 {
-                if EXPLORATION_RATE.with_borrow(|erate| -> bool {*erate > 0.1}) {
-                    EXPLORATION_RATE.with_borrow_mut(|erate| {
-                        *erate -= 0.1;
-                    });
-                }
-            }
-            if self.episode > 10_000 && self.episode % 5_000 == 0 {
-                K.with_borrow_mut(|k| {
-                    *k += 0.05;
-                });
+    fn evaluate_progress(&mut self, q: &mut QTable) {
+        let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+        let opponent = Box::new(evaluate::reference_opponent(self.spec));
+        let options = EvalOptions {
+            spec: self.spec,
+            seed: Some(self.episode as u64),
+            ..EvalOptions::default()
+        };
+        let report = evaluate::evaluate(rl_max, q, opponent, &mut QTable::new(), self.config.eval_games, &options);
+        println!("episode {:?}: {report}", self.episode);
+        self.evaluations.push((self.episode, report));
+    }
+    fn learn_q_table(&mut self, mut q: Option<&mut QTable>) {
+        let mut metrics = MetricsLog::open(&self.config, self.episode > 0);
+        while self.episode < self.config.num_episodes {
+            let stats = self.learn_episode(q.as_deref_mut());
+            let params = self.config.at(self.episode - 1);
+            metrics.record(self.episode, &stats, q.as_deref().unwrap().len(), &params);
+            if self.config.eval_every.is_some_and(|every| self.episode.is_multiple_of(every)) {
+                self.evaluate_progress(q.as_deref_mut().unwrap());
             }
-            if self.episode >= NUM_EPISODES {
-                println!("episode {:?}", self.episode);
-                break;
+            if self.config.checkpoint_every.is_some_and(|every| self.episode.is_multiple_of(every)) {
+                let checkpoint = self.checkpoint(q.as_deref().unwrap());
+                if let Err(e) = checkpoint.save(&self.config.checkpoint_path) {
+                    println!("Could not save the checkpoint: {e}");
+                }
             }
-            self.learn_episode_with_minimax(q.as_deref_mut());
         }
+        println!("episode {:?}", self.episode);
         let path = Path::new("./q_table_archive/");
-        let is_q_saved = q_table::q_table_to_disk(&path, q.as_deref().unwrap());
+        if let Err(e) = q_table::q_table_to_disk(path, q.as_deref().unwrap()) {
+            println!("Could not save the QTable: {e}");
+        }
     }
 }
 pub fn train_rl_agent() {
-    let mut rl_max = Box::new(ComputerPlayerRLmax {
-        name: "RLmax".to_string(),
-        mark: Marks::None,
-    });
-    let mut rl_min = Box::new(ComputerPlayerRLmin {
-        name: "RLmin".to_string(),
-        mark: Marks::None,
-    });
-    let mut game = Game::new(rl_max, rl_min);
-    let mut q = QTable::new();
+    train_rl_agent_with_config(TrainingConfig::default());
+}
+
+pub fn train_rl_agent_with_seed(seed: u64) -> QTable {
+    train_rl_agent_with_config(TrainingConfig {
+        seed: Some(seed),
+        ..TrainingConfig::default()
+    })
+}
+
+pub fn train_rl_agent_with_config(config: TrainingConfig) -> QTable {
+    let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+    let rl_min = Box::new(ComputerPlayerRLmin::new("RLmin".to_string()));
+    let mut game = Game::new(rl_max, rl_min).with_config(config);
+    let mut q = QTable::with_symmetry();
+    game.learn_q_table(Some(&mut q));
+    q
+}
+
+pub fn resume_rl_agent(path: &Path) -> Result<QTable, anyhow::Error> {
+    let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+    let rl_min = Box::new(ComputerPlayerRLmin::new("RLmin".to_string()));
+    let (mut game, mut q) = Game::resume(rl_max, rl_min, Checkpoint::load(path)?);
     game.learn_q_table(Some(&mut q));
+    Ok(q)
 }
 
 pub fn train_rl_agent_with_minimax() {
-    let mut rl_max = Box::new(ComputerPlayerRLmax {
-        name: "RLmax".to_string(),
-        mark: Marks::None,
-    });
-    let path = Path::new("./q_table_archive/qtable-max");
-    let mut q_max = RefCell::new(q_table::q_table_from_disk_pickle(&path)
-        .expect("The QTable for minimax should be present"));
-    let mut rl_min = Box::new(MinimaxPlayer {
-        name: "minimax".to_string(),
-        mark: Marks::None,
-        q_max,
-    });
+    let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+    let rl_min = Box::new(MinimaxPlayer::perfect("minimax".to_string(), BoardSpec::default()));
     let mut game = Game::new(rl_max, rl_min);
     let mut q = QTable::new();
     game.learn_q_table(Some(&mut q));
-    // let path = Path::new("./q_table_archive/qtable_max");
-    // if game.current_player.get_name() == "minimax" {
-    //     Box::leak(game.current_player).q_to_disk().expect("Saving minimax QTable to disk")
-    // } else {
-    //     Box::leak(game.other_player).q_to_disk().expect("Saving minimax QTable to disk")};
+}
 
+pub fn train_rl_agent_in_league(config: TrainingConfig, league: LeagueConfig) -> Result<QTable, anyhow::Error> {
+    let mut league = League::new(BoardSpec::default(), league)?;
+    let q = league.train(config);
+    print!("{league}");
+    Ok(q)
+}
+
+fn announce_result(result: &GameResult, human: &str) {
+    match result.winner_name.as_deref() {
+        None => println!("The game ended in a draw."),
+        Some(name) if name == human => println!("Congratulations, {name}! You have won!"),
+        Some(_) => println!("Really sorry, {human}, you have lost."),
+    }
 }
 
 pub fn play_game_2_humans() {
@@ -296,143 +425,335 @@ pub fn play_game_2_humans() {
     });
     let mut game = Game::new(player_1, player_2);
     let mut q = QTable::new();
-    loop {
-        if game.board.is_full() {break;}
-        let mv = game.current_player.choose_move(&game.board, &mut q);
-        game.current_player.make_move(&mut game.board, &mv);
-        let is_over = game.board.is_game_over(&game.current_player.get_mark());
-        match is_over {
-            IsGameOver::InPlay => {
-                game.swap_players();
-                game.swap_states();
-            }
-            IsGameOver::Drawn => {
-                println!("The game ended in a draw.");
-                break;
-            }
-            IsGameOver::Win => {
-                if game.current_player.get_name() == "Bob" {
-                    println!("Congratulations, Bob! You have won!");
-                    break;
-                } else {
-                    println!("Congratulations, Alice! You have won!");
-                    break;
-                }
-            }
-        }
+    // The players picked their own marks, so the board is played as it was set up.
+    let result = game.play_turns(&mut q, None);
+    match result.winner_name {
+        Some(name) => println!("Congratulations, {name}! You have won!"),
+        None => println!("The game ended in a draw."),
     }
 }
 
 pub fn play_game_human_computer_player() {
-    EXPLORATION_RATE.replace(0.0_f32);
     let player_1 = Box::new(HumanPlayer::new("John".to_owned()));
     let player_2 = Box::new(ComputerPlayerRLmax {
-        name: "RLmax".to_owned(),
-        mark: Marks::None,
+        params: Hyperparameters::greedy(),
+        ..ComputerPlayerRLmax::new("RLmax".to_owned())
     });
     let mut game = Game::new(player_1, player_2);
     let path = Path::new("./q_table_archive/qtable");
-    let mut q = q_table::q_table_from_disk_pickle(&path).expect("QTable is always present");
+    let mut q = q_table::q_table_from_disk_pickle(path).expect("QTable is always present");
     println!("QTable's length is: {:?}", q.len());
-    loop {
-        let mv = game.current_player.choose_move(&game.board, &mut q);
-        game.current_player.make_move(&mut game.board, &mv);
-        let is_over = game.board.is_game_over(&game.current_player.get_mark());
-        match is_over {
-            IsGameOver::InPlay => {
-                game.swap_players();
-                game.swap_states();
-            }
-            IsGameOver::Drawn => {
-                println!("The game ended in a draw.");
-                break;
-            }
-            IsGameOver::Win => {
-                if game.current_player.get_name() == "John" {
-                    println!("Congratulations, John! You have won!");
-                    break;
-                } else {
-                    println!("Really sorry, John, you have lost.");
-                    break;
-                }
-            }
-        }
-     }
+    let result = game.play_turns(&mut q, None);
+    announce_result(&result, "John");
 }
 
 pub fn play_human_minimax() {
-    let path = Path::new("./q_table_archive/qtable-max");
-    let mut q_max = RefCell::new(q_table::q_table_from_disk_pickle(&path)
-        .expect("The QTable for minimax should be present"));
     let player_1 = Box::new(HumanPlayer::new("John".to_owned()));
-    let player_2 = Box::new(MinimaxPlayer {
-        name: "MinMax".to_owned(),
-        mark: player_1.mark.other(),
-        q_max,
-    });
+    let mut player_2 = Box::new(MinimaxPlayer::perfect("MinMax".to_owned(), BoardSpec::default()));
+    player_2.set_mark(player_1.mark.other());
     let mut game = Game::new(player_1, player_2);
     let mut q = QTable::new();
-    loop {
-        let mv = game.current_player.choose_move(&game.board, &mut q);
-        game.current_player.make_move(&mut game.board, &mv);
-        let is_over = game.board.is_game_over(&game.current_player.get_mark());
-        match is_over {
-            IsGameOver::InPlay => {
-                game.swap_players();
-                game.swap_states();
-            }
-            IsGameOver::Drawn => {
-                println!("The game ended in a draw.");
-                break;
-            }
-            IsGameOver::Win => {
-                if game.current_player.get_name() == "John" {
-                    println!("Congratulations, John! You have won!");
-                    break;
-                } else {
-                    println!("Really sorry, John, you have lost.");
-                    break;
-                }
-            }
-        }
-     }
+    let result = game.play_turns(&mut q, None);
+    announce_result(&result, "John");
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    // A fresh RLmax/RLmin training game under `config`, with the symmetric table the learners train into.
+    pub(crate) fn seeded_game(config: TrainingConfig) -> (Game, QTable) {
+        let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+        let rl_min = Box::new(ComputerPlayerRLmin::new("RLmin".to_string()));
+        (Game::new(rl_max, rl_min).with_config(config), QTable::with_symmetry())
+    }
+
     #[test]
-    fn is_minimax_player_working(){
-        let mut minimax1 = Box::new(MinimaxPlayer {
-            name: "Minimax1".to_string(),
-            mark: Marks::None,
-        });
-        let mut minimax2 = Box::new(MinimaxPlayer {
-            name: "Minimax2".to_string(),
-            mark: Marks::None,
-        });
+    fn is_minimax_player_working() {
+        let minimax1 = Box::new(MinimaxPlayer::new("Minimax1".to_string(), QTable::new()));
+        let minimax2 = Box::new(MinimaxPlayer::new("Minimax2".to_string(), QTable::new()));
         let mut game = Game::new(minimax1, minimax2);
         game.current_player.set_mark(Marks::NOUGHT);
         println!("Game initiation minimax1 name {:?}, mark {:?}, minimax2 name {:?}, mark {:?}",
             game.current_player.get_name(), game.current_player.get_mark(),
             game.other_player.get_name(), game.other_player.get_mark());
         let mut q = QTable::new();
-        let mv = game.current_player.choose_move(&game.board, &mut q);
+        let mv = game.current_player.choose_move(&game.board, &mut q, &mut game.rng);
         println!("After choosing the first move: move {:?}, QTable {:?}", mv, q);
     }
 
     #[test]
     fn is_game_working() {
-        let mut rl_max = Box::new(ComputerPlayerRLmax {
-            name: "RLmax".to_string(),
-            mark: Marks::None,
-        });
-        let mut rl_min = Box::new(ComputerPlayerRLmin {
-            name: "RLmin".to_string(),
-            mark: Marks::None,
-        });
+        let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+        let rl_min = Box::new(ComputerPlayerRLmin::new("RLmin".to_string()));
         let mut game = Game::new(rl_max, rl_min);
         let mut q = QTable::new();
         game.learn_q_table(Some(&mut q));
     }
+
+    #[test]
+    fn is_game_with_spec_working() {
+        let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+        let rl_min = Box::new(ComputerPlayerRLmin::new("RLmin".to_string()));
+        let spec = BoardSpec::new(6, 7, 4);
+        let mut game = Game::with_spec(rl_max, rl_min, spec);
+        let mut q = QTable::new();
+        for _ in 0..100 {
+            game.learn_episode(Some(&mut q));
+        }
+        assert_eq!(game.episode, 100);
+        assert!(q.keys().all(|key| key.starts_with(|c| "X0-".contains(c)) && key.len() == 42 + 5));
+    }
+
+    #[test]
+    fn is_seeded_training_working() {
+        let train = |seed: u64| {
+            let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+            let rl_min = Box::new(ComputerPlayerRLmin::new("RLmin".to_string()));
+            let mut game = Game::with_seed(rl_max, rl_min, BoardSpec::default(), seed);
+            let mut q = QTable::with_symmetry();
+            for _ in 0..500 {
+                game.learn_episode(Some(&mut q));
+            }
+            serde_json::to_string(&q).unwrap()
+        };
+        assert_eq!(train(42), train(42));
+        assert_ne!(train(42), train(43));
+    }
+
+    #[test]
+    fn is_bitboard_training_working() {
+        let train = |spec: BoardSpec, bitboard: bool, mut q: QTable| {
+            let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+            let rl_min = Box::new(ComputerPlayerRLmin::new("RLmin".to_string()));
+            let mut game = Game::with_seed(rl_max, rl_min, spec, 42).with_config(TrainingConfig {
+                bitboard,
+                ..TrainingConfig::default()
+            });
+            for _ in 0..300 {
+                game.learn_episode(Some(&mut q));
+            }
+            serde_json::to_string(&q).unwrap()
+        };
+        let spec = BoardSpec::new(4, 5, 3);
+        assert_eq!(train(spec, true, QTable::new()), train(spec, false, QTable::new()));
+        assert_eq!(train(spec, true, QTable::with_symmetry()), train(spec, false, QTable::with_symmetry()));
+        // Too many squares for a bitboard, so training stays on the char array.
+        let spec = BoardSpec::new(8, 17, 5);
+        assert_eq!(train(spec, true, QTable::new()), train(spec, false, QTable::new()));
+    }
+
+    #[test]
+    fn is_training_config_working() {
+        let configs = [0.0, 1.0].map(|epsilon| TrainingConfig {
+            num_episodes: 300,
+            seed: Some(5),
+            softmax_after: 200,
+            epsilon: config::Schedule::Constant { value: epsilon },
+            ..TrainingConfig::default()
+        });
+        let handles = configs.map(|config| {
+            std::thread::spawn(move || {
+                let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+                let rl_min = Box::new(ComputerPlayerRLmin::new("RLmin".to_string()));
+                let mut game = Game::new(rl_max, rl_min).with_config(config);
+                let mut q = QTable::new();
+                while game.episode < game.config.num_episodes {
+                    game.learn_episode(Some(&mut q));
+                }
+                q.len()
+            })
+        });
+        let [greedy, random] = handles.map(|handle| handle.join().unwrap());
+        assert!(greedy < random);
+    }
+
+    #[test]
+    fn is_checkpoint_working() {
+        let players = || -> (Box<dyn Player>, Box<dyn Player>) {
+            (
+                Box::new(ComputerPlayerRLmax::new("RLmax".to_string())),
+                Box::new(ComputerPlayerRLmin::new("RLmin".to_string())),
+            )
+        };
+        let config = TrainingConfig {
+            seed: Some(11),
+            ..TrainingConfig::default()
+        };
+        let (rl_max, rl_min) = players();
+        let mut game = Game::new(rl_max, rl_min).with_config(config);
+        let mut q = QTable::with_symmetry();
+        for _ in 0..200 {
+            game.learn_episode(Some(&mut q));
+        }
+        let path = std::env::temp_dir().join("learn_game_checkpoint_test.json");
+        game.checkpoint(&q).save(&path).unwrap();
+        for _ in 0..200 {
+            game.learn_episode(Some(&mut q));
+        }
+
+        let (rl_max, rl_min) = players();
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        assert_eq!((checkpoint.episode, checkpoint.config.at(checkpoint.episode).epsilon), (200, 0.9));
+        let (mut resumed, mut resumed_q) = Game::resume(rl_max, rl_min, checkpoint);
+        assert!(resumed_q.is_symmetric());
+        for _ in 0..200 {
+            resumed.learn_episode(Some(&mut resumed_q));
+        }
+        assert_eq!(resumed.episode, 400);
+        assert_eq!(serde_json::to_string(&q).unwrap(), serde_json::to_string(&resumed_q).unwrap());
+    }
+
+    #[test]
+    fn is_periodic_evaluation_working() {
+        let (mut game, mut q) = seeded_game(TrainingConfig {
+            num_episodes: 300,
+            seed: Some(1),
+            eval_every: Some(100),
+            eval_games: 20,
+            ..TrainingConfig::default()
+        });
+        game.learn_q_table(Some(&mut q));
+        let episodes: Vec<usize> = game.evaluations.iter().map(|(episode, _)| *episode).collect();
+        assert_eq!(episodes, vec![100, 200, 300]);
+        assert!(game.evaluations.iter().all(|(_, report)| report.total().wins == 0));
+    }
+
+    #[test]
+    fn is_metrics_export_working() {
+        for extension in ["csv", "jsonl"] {
+            let path = std::env::temp_dir().join(format!("learn_game_metrics_test.{extension}"));
+            let _ = std::fs::remove_file(&path);
+            let config = TrainingConfig {
+                num_episodes: 200,
+                seed: Some(2),
+                metrics_every: Some(50),
+                metrics_path: path.clone(),
+                ..TrainingConfig::default()
+            };
+            let (mut game, mut q) = seeded_game(config.clone());
+            game.learn_q_table(Some(&mut q));
+            // A second fresh run replaces the first one's rows.
+            let (mut fresh, mut fresh_q) = seeded_game(config);
+            fresh.learn_q_table(Some(&mut fresh_q));
+            let text = std::fs::read_to_string(&path).unwrap();
+            let lines: Vec<&str> = text.lines().collect();
+            if extension == "csv" {
+                assert_eq!(lines.len(), 5);
+                assert_eq!(lines[0], metrics::MetricsRecord::CSV_HEADER);
+                assert!(lines[4].starts_with("200,50,"));
+            } else {
+                assert_eq!(lines.len(), 4);
+                let last: serde_json::Value = serde_json::from_str(lines[3]).unwrap();
+                assert_eq!(last["q_size"], q.len());
+                let rates = ["cross_win_rate", "nought_win_rate", "draw_rate"];
+                let total: f64 = rates.iter().map(|rate| last[rate].as_f64().unwrap()).sum();
+                assert!((total - 1.0).abs() < 1e-4);
+                assert!(last["mean_abs_td_error"].as_f64().unwrap() > 0.0);
+            }
+
+            // Resuming from a checkpoint carries on after the rows already written.
+            let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+            let rl_min = Box::new(ComputerPlayerRLmin::new("RLmin".to_string()));
+            let (mut resumed, mut resumed_q) = Game::resume(rl_max, rl_min, game.checkpoint(&q));
+            resumed.config.num_episodes = 300;
+            resumed.learn_q_table(Some(&mut resumed_q));
+            let text = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(text.lines().count(), lines.len() + 2);
+            assert_eq!(text.matches(metrics::MetricsRecord::CSV_HEADER).count(), usize::from(extension == "csv"));
+        }
+    }
+
+    #[test]
+    fn is_softmax_sampling_working() {
+        let (mut game, mut q) = seeded_game(TrainingConfig {
+            seed: Some(2),
+            softmax_after: 0,
+            temperature: config::Schedule::Constant { value: 0.5 },
+            ..TrainingConfig::default()
+        });
+        game.learn_episode(Some(&mut q));
+        let mut moves = Vec::new();
+        for _ in 0..50 {
+            let stats = game.learn_episode(Some(&mut q));
+            assert!(stats.sampled_moves >= 5 && stats.entropy > 0.0);
+            moves.push(game.board.next_state.to_state_key());
+        }
+        // Sampling keeps the final positions varied instead of replaying the argmax.
+        moves.sort();
+        moves.dedup();
+        assert!(moves.len() > 10);
+    }
+
+    #[test]
+    fn is_exploration_working() {
+        let strategies = [
+            Exploration::EpsilonGreedy,
+            Exploration::Ucb { c: 1.0 },
+            Exploration::Optimistic { value: 1.0 },
+            Exploration::CountBased { c: 5.0 },
+        ];
+        let tables = strategies.map(|exploration| {
+            let (mut game, mut q) = seeded_game(TrainingConfig {
+                seed: Some(12),
+                exploration,
+                ..TrainingConfig::default()
+            });
+            let mut played = 0;
+            for _ in 0..200 {
+                game.learn_episode(Some(&mut q));
+                played += game.spec.cells() - game.board.next_state.available_moves().len();
+            }
+            let visits: u32 = q.values().map(|moves| moves.total_visits()).sum();
+            assert_eq!(visits as usize, played);
+            q
+        });
+        // Optimistic entries start high for RLmax and low for RLmin, and stay there until tried.
+        let untried = |suffix: &str, value: f32| {
+            tables[2]
+                .iter()
+                .filter(|(key, _)| key.ends_with(suffix))
+                .all(|(_, moves)| moves.iter().all(|(mv, v)| *v == value || moves.visits_of(mv) > 0))
+        };
+        assert!(untried("RLmax", 1.0) && untried("RLmin", -1.0));
+        let empty = q_table::QTable::new().state_key(&board::GameState::new(), "RLmax").key;
+        // Unlike uniform epsilon, UCB tries every opening move within the first few games.
+        assert_eq!(tables[1].get(&empty).unwrap().visits.len(), 9);
+        let encoded = tables.map(|q| serde_json::to_string(&q).unwrap());
+        assert_ne!(encoded[0], encoded[1]);
+        assert_ne!(encoded[0], encoded[3]);
+    }
+
+    #[test]
+    fn is_play_to_end_working() {
+        let minimax = Box::new(MinimaxPlayer::perfect("Minimax".to_string(), BoardSpec::default()));
+        let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+        let mut game = Game::new(rl_max, minimax);
+        let mut q = QTable::new();
+        let result = game.play_to_end(&mut q);
+        assert_ne!(result.winner_name.as_deref(), Some("RLmax"));
+        assert_eq!(result.moves[0].0, Marks::CROSS);
+        assert_eq!(result.final_state.iter().filter(|&&c| c != '-').count(), result.moves.len());
+        for (mark, (a, b)) in &result.moves {
+            assert_eq!(result.final_state[[*a, *b]], mark.as_char());
+        }
+    }
+
+    #[test]
+    fn is_repeated_play_to_end_working() {
+        let minimax = Box::new(MinimaxPlayer::perfect("Minimax".to_string(), BoardSpec::default()));
+        let rl_max = Box::new(ComputerPlayerRLmax::new("RLmax".to_string()));
+        let mut game = Game::with_seed(rl_max, minimax, BoardSpec::default(), 5);
+        let mut q = QTable::new();
+        let mut rl_marks = Vec::new();
+        for _ in 0..6 {
+            let result = game.play_to_end(&mut q);
+            assert_eq!(result.moves[0].0, Marks::CROSS);
+            assert_eq!(result.final_state.iter().filter(|&&c| c != '-').count(), result.moves.len());
+            assert_ne!(result.winner_name.as_deref(), Some("RLmax"));
+            let rl_max = [&game.current_player, &game.other_player].into_iter().find(|p| p.get_name() == "RLmax");
+            rl_marks.push(*rl_max.unwrap().get_mark());
+        }
+        assert!(rl_marks.contains(&Marks::CROSS) && rl_marks.contains(&Marks::NOUGHT));
+    }
 }
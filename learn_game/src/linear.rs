@@ -0,0 +1,192 @@
+use crate::afterstate::{self, ValueFunction};
+use crate::board::{BoardSpec, GameState};
+use crate::config::{Hyperparameters, TrainingConfig};
+use crate::players::Marks;
+use crate::search;
+use ndarray::{Array1, ArrayView1, ArrayViewMut1};
+use serde::{Deserialize, Serialize};
+
+// A bias plus the same eight features for X and for 0.
+pub const NUM_FEATURES: usize = 17;
+
+// A linear estimate of the afterstate value from X's point of view, so it works on any board size.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LinearValue {
+    pub spec: BoardSpec,
+    pub weights: Vec<f32>,
+}
+
+// Hand-crafted features of a position, each scaled into [0, 1].
+pub fn features(state: &GameState) -> Array1<f32> {
+    let mut phi = Array1::zeros(NUM_FEATURES);
+    phi[0] = 1.0;
+    for (i, mark) in [Marks::CROSS, Marks::NOUGHT].iter().enumerate() {
+        let offset = 1 + i * 8;
+        for (j, value) in mark_features(state, mark).into_iter().enumerate() {
+            phi[offset + j] = value;
+        }
+    }
+    phi
+}
+
+// Walks the winning lines over the char array, so boards too large for a bitboard are featurized too.
+fn mark_features(state: &GameState, mark: &Marks) -> [f32; 8] {
+    let spec = state.spec;
+    let lines = search::lines_for(spec);
+    let (mine, theirs) = (mark.as_char(), mark.other().as_char());
+    let k = spec.win_len;
+    let mut open = 0;
+    let mut threats = 0;
+    let mut developing = 0;
+    let mut won = false;
+    // Empty squares that would complete a developing line, counted per line through them.
+    let mut fork_squares = vec![0_u32; spec.cells()];
+    for line in lines.iter().filter(|line| line.iter().all(|&(r, c)| state[[r, c]] != theirs)) {
+        let count = line.iter().filter(|&&(r, c)| state[[r, c]] == mine).count();
+        match count {
+            0 => continue,
+            c if c == k => won = true,
+            c if c + 1 == k => threats += 1,
+            c if c + 2 == k => {
+                developing += 1;
+                for &(r, c) in line.iter().filter(|&&(r, c)| state[[r, c]] != mine) {
+                    fork_squares[r * spec.cols + c] += 1;
+                }
+            }
+            _ => {}
+        }
+        open += 1;
+    }
+    // A move that turns two developing lines into threats at once is a fork.
+    let forks = fork_squares.iter().filter(|&&n| n >= 2).count();
+    let total = lines.len().max(1) as f32;
+    let held = |cells: &[(usize, usize)]| {
+        cells.iter().filter(|&&(r, c)| state[[r, c]] == mine).count() as f32 / cells.len() as f32
+    };
+    // A threat is only decisive for the side to move, which a linear model cannot work out on its own.
+    let to_move = state.side_to_move() == *mark && threats > 0;
+    [
+        open as f32 / total,
+        (threats.min(2) as f32) / 2.0,
+        if to_move { 1.0 } else { 0.0 },
+        developing as f32 / total,
+        (forks.min(2) as f32) / 2.0,
+        held(&center_squares(spec)),
+        held(&corner_squares(spec)),
+        if won { 1.0 } else { 0.0 },
+    ]
+}
+
+fn center_squares(spec: BoardSpec) -> Vec<(usize, usize)> {
+    let middle = |n: usize| if n % 2 == 1 { vec![n / 2] } else { vec![n / 2 - 1, n / 2] };
+    let (rows, cols) = (middle(spec.rows), middle(spec.cols));
+    rows.iter().flat_map(|&r| cols.iter().map(move |&c| (r, c))).collect()
+}
+
+fn corner_squares(spec: BoardSpec) -> Vec<(usize, usize)> {
+    let (r, c) = (spec.rows - 1, spec.cols - 1);
+    vec![(0, 0), (0, c), (r, 0), (r, c)]
+}
+
+impl LinearValue {
+    pub fn new(spec: BoardSpec) -> Self {
+        LinearValue {
+            spec,
+            weights: vec![0.0; NUM_FEATURES],
+        }
+    }
+}
+
+impl ValueFunction for LinearValue {
+    fn value(&self, state: &GameState) -> f32 {
+        ArrayView1::from(&self.weights).dot(&features(state))
+    }
+    // Semi-gradient TD: the target is treated as fixed. The step is divided by the squared feature norm,
+    // so one update never overshoots the target however many features are active.
+    fn update_toward(&mut self, state: &GameState, expected: f32, params: &Hyperparameters) -> f32 {
+        let phi = features(state);
+        let td_error = expected - ArrayView1::from(&self.weights).dot(&phi);
+        let step = params.alpha * td_error / phi.dot(&phi);
+        ArrayViewMut1::from(&mut self.weights).scaled_add(step, &phi);
+        td_error
+    }
+}
+
+pub fn train(config: &TrainingConfig, spec: BoardSpec) -> LinearValue {
+    let mut values = LinearValue::new(spec);
+    afterstate::learn(&mut values, config, spec);
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::afterstate::AfterstatePlayer;
+    use crate::board::Board;
+    use crate::config::Schedule;
+    use crate::players::Player;
+    use crate::q_table::QTable;
+    use crate::rng;
+
+    #[test]
+    fn is_features_working() {
+        let mut state = GameState::new();
+        state[[0, 0]] = 'X';
+        state[[0, 1]] = 'X';
+        state[[2, 2]] = 'X';
+        state[[1, 1]] = '0';
+        let phi = features(&state);
+        assert_eq!(phi[0], 1.0);
+        // X: a threat on the top row that 0 must answer, corners (0, 0) and (2, 2), and a fork at (2, 0).
+        let x = vec![4.0 / 8.0, 0.5, 0.0, 3.0 / 8.0, 0.5, 0.0, 0.5, 0.0];
+        assert_eq!(phi.slice(ndarray::s![1..9]).to_vec(), x);
+        // 0 holds the center, with only the middle row and one diagonal still open.
+        let nought = vec![2.0 / 8.0, 0.0, 0.0, 2.0 / 8.0, 0.0, 1.0, 0.0, 0.0];
+        assert_eq!(phi.slice(ndarray::s![9..17]).to_vec(), nought);
+        assert_eq!(center_squares(BoardSpec::square(4)).len(), 4);
+
+        // Too large for a bitboard: four in a row threatens, the fifth wins.
+        let mut state = GameState::with_spec(BoardSpec::new(8, 17, 5));
+        for c in 0..4 {
+            state[[7, c]] = 'X';
+            state[[0, c]] = '0';
+        }
+        let phi = features(&state);
+        assert_eq!((phi[2], phi[3], phi[8]), (0.5, 1.0, 0.0));
+        state[[7, 4]] = 'X';
+        assert_eq!(features(&state)[8], 1.0);
+    }
+
+    #[test]
+    fn is_linear_value_working() {
+        let config = TrainingConfig {
+            num_episodes: 300,
+            seed: Some(1),
+            epsilon: Schedule::Constant { value: 0.2 },
+            ..TrainingConfig::default()
+        };
+        let spec = BoardSpec::new(5, 5, 4);
+        let mut player = AfterstatePlayer::new("Linear".to_string(), train(&config, spec));
+        assert!(player.values.weights.iter().all(|w| w.is_finite()));
+        let mut board = Board::with_spec(spec);
+        for c in 0..3 {
+            board.current_state[[0, c]] = 'X';
+            board.current_state[[2, c]] = '0';
+        }
+        let mut q = QTable::new();
+        let mut rng = rng::seeded(2);
+        player.set_mark(Marks::CROSS);
+        assert_eq!(player.choose_move(&board, &mut q, &mut rng), (0, 3));
+        board.current_state[[2, 0]] = '-';
+        player.set_mark(Marks::NOUGHT);
+        assert_eq!(player.choose_move(&board, &mut q, &mut rng), (0, 3));
+
+        // Larger boards only cost more features to compute, not more storage.
+        let config = TrainingConfig {
+            num_episodes: 5,
+            ..config
+        };
+        let values = train(&config, BoardSpec::new(7, 7, 5));
+        assert_eq!(values.weights.len(), NUM_FEATURES);
+    }
+}
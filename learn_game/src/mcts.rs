@@ -0,0 +1,363 @@
+use crate::bitboard::BitBoard;
+use crate::board::Board;
+use crate::players::{Marks, Player};
+use crate::q_table::QTable;
+use crate::rng::GameRng;
+use crate::search::Position;
+use rand::prelude::SliceRandom;
+use rand::{Rng, RngCore};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Budget {
+    Iterations(usize),
+    Time(Duration),
+}
+
+pub trait RolloutPolicy: fmt::Debug {
+    fn choose(&self, position: &mut dyn Position, to_move: &Marks, rng: &mut dyn RngCore) -> (usize, usize);
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomRollout;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WinOrBlockRollout;
+
+#[derive(Debug, Clone)]
+pub struct Node<P = BitBoard> {
+    pub position: P,
+    pub to_move: Marks,
+    pub mv: Option<(usize, usize)>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub untried: Vec<(usize, usize)>,
+    pub visits: u32,
+    pub reward: f32,
+    pub terminal: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct MctsTree<P = BitBoard> {
+    pub nodes: Vec<Node<P>>,
+}
+
+#[derive(Debug)]
+pub struct MctsPlayer {
+    pub name: String,
+    pub mark: Marks,
+    pub budget: Budget,
+    pub exploration: f32,
+    pub rollout: Box<dyn RolloutPolicy>,
+    // Kept between moves on bitboards only; larger boards search from scratch each move.
+    pub tree: RefCell<Option<MctsTree>>,
+}
+
+impl RolloutPolicy for RandomRollout {
+    fn choose(&self, position: &mut dyn Position, _to_move: &Marks, rng: &mut dyn RngCore) -> (usize, usize) {
+        let n = position.spec().cells() - position.stones();
+        position.nth_move(rng.gen_range(0..n))
+    }
+}
+
+impl RolloutPolicy for WinOrBlockRollout {
+    fn choose(&self, position: &mut dyn Position, to_move: &Marks, rng: &mut dyn RngCore) -> (usize, usize) {
+        let moves = position.moves();
+        for mark in [*to_move, to_move.other()] {
+            for mv in &moves {
+                position.play(mv, &mark);
+                let wins = position.is_win_through(&mark, mv);
+                position.undo(mv);
+                if wins {
+                    return *mv;
+                }
+            }
+        }
+        RandomRollout.choose(position, to_move, rng)
+    }
+}
+
+impl<P: Position + Clone + PartialEq> Node<P> {
+    fn new(position: P, to_move: Marks, mv: Option<(usize, usize)>, parent: Option<usize>) -> Self {
+        let terminal = position.is_win(&to_move.other()) || position.is_full();
+        let untried = if terminal { Vec::new() } else { position.moves() };
+        Node {
+            position,
+            to_move,
+            mv,
+            parent,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            reward: 0.0,
+            terminal,
+        }
+    }
+}
+
+impl<P: Position + Clone + PartialEq> MctsTree<P> {
+    pub fn new(position: P, to_move: Marks) -> Self {
+        MctsTree {
+            nodes: vec![Node::new(position, to_move, None, None)],
+        }
+    }
+
+    pub fn root(&self) -> &Node<P> {
+        &self.nodes[0]
+    }
+
+    fn find(&self, position: &P, max_depth: usize) -> Option<usize> {
+        let mut queue = VecDeque::from([(0_usize, 0_usize)]);
+        while let Some((idx, depth)) = queue.pop_front() {
+            if self.nodes[idx].position == *position {
+                return Some(idx);
+            }
+            if depth < max_depth {
+                queue.extend(self.nodes[idx].children.iter().map(|&c| (c, depth + 1)));
+            }
+        }
+        None
+    }
+
+    fn subtree(&self, idx: usize) -> MctsTree<P> {
+        let mut nodes: Vec<Node<P>> = Vec::new();
+        let mut queue = VecDeque::from([(idx, None)]);
+        while let Some((old, parent)) = queue.pop_front() {
+            let new_idx = nodes.len();
+            let mut node = self.nodes[old].clone();
+            node.parent = parent;
+            node.children.clear();
+            if let Some(p) = parent {
+                nodes[p].children.push(new_idx);
+            }
+            queue.extend(self.nodes[old].children.iter().map(|&c| (c, Some(new_idx))));
+            nodes.push(node);
+        }
+        nodes[0].mv = None;
+        MctsTree { nodes }
+    }
+
+    pub fn reuse(tree: Option<MctsTree<P>>, position: &P, to_move: Marks) -> MctsTree<P> {
+        match tree.as_ref().and_then(|t| t.find(position, 2).map(|idx| (t, idx))) {
+            Some((t, idx)) => t.subtree(idx),
+            None => MctsTree::new(position.clone(), to_move),
+        }
+    }
+
+    fn select_child(&self, idx: usize, exploration: f32) -> usize {
+        let log_parent = (self.nodes[idx].visits.max(1) as f32).ln();
+        *self.nodes[idx]
+            .children
+            .iter()
+            .max_by(|&&a, &&b| {
+                let uct = |i: usize| {
+                    let n = &self.nodes[i];
+                    n.reward / n.visits as f32 + exploration * (log_parent / n.visits as f32).sqrt()
+                };
+                uct(a).total_cmp(&uct(b))
+            })
+            .unwrap()
+    }
+
+    pub fn iterate(&mut self, exploration: f32, rollout: &dyn RolloutPolicy, rng: &mut dyn RngCore) {
+        let mut idx = 0;
+        while self.nodes[idx].untried.is_empty() && !self.nodes[idx].terminal {
+            idx = self.select_child(idx, exploration);
+        }
+        if !self.nodes[idx].terminal {
+            let untried = &mut self.nodes[idx].untried;
+            let mv = untried.swap_remove(rng.gen_range(0..untried.len()));
+            let (mut position, to_move) = (self.nodes[idx].position.clone(), self.nodes[idx].to_move);
+            position.play(&mv, &to_move);
+            let child = self.nodes.len();
+            self.nodes.push(Node::new(position, to_move.other(), Some(mv), Some(idx)));
+            self.nodes[idx].children.push(child);
+            idx = child;
+        }
+        let winner = Self::simulate(self.nodes[idx].position.clone(), self.nodes[idx].to_move, rollout, rng);
+        let mut current = Some(idx);
+        while let Some(i) = current {
+            let node = &mut self.nodes[i];
+            node.visits += 1;
+            node.reward += match winner {
+                Some(mark) if mark == node.to_move.other() => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            current = node.parent;
+        }
+    }
+
+    fn simulate(
+        mut position: P,
+        mut to_move: Marks,
+        rollout: &dyn RolloutPolicy,
+        rng: &mut dyn RngCore,
+    ) -> Option<Marks> {
+        if position.is_win(&to_move.other()) {
+            return Some(to_move.other());
+        }
+        while !position.is_full() {
+            let mv = rollout.choose(&mut position, &to_move, rng);
+            position.play(&mv, &to_move);
+            if position.is_win_through(&to_move, &mv) {
+                return Some(to_move);
+            }
+            to_move = to_move.other();
+        }
+        None
+    }
+
+    pub fn best_move(&self, rng: &mut dyn RngCore) -> (usize, usize) {
+        let root = self.root();
+        let best = root
+            .children
+            .iter()
+            .map(|&c| &self.nodes[c])
+            .max_by_key(|n| n.visits)
+            .and_then(|n| n.mv);
+        best.unwrap_or_else(|| *root.untried.choose(rng).unwrap())
+    }
+}
+
+impl MctsPlayer {
+    pub fn new(name: String, budget: Budget) -> Self {
+        MctsPlayer {
+            name,
+            mark: Marks::None,
+            budget,
+            exploration: std::f32::consts::SQRT_2,
+            rollout: Box::new(RandomRollout),
+            tree: RefCell::new(None),
+        }
+    }
+    pub fn with_rollout(mut self, rollout: Box<dyn RolloutPolicy>) -> Self {
+        self.rollout = rollout;
+        self
+    }
+    pub fn search(&self, board: &Board, rng: &mut GameRng) -> (usize, usize) {
+        match board.current_state.to_bitboard() {
+            Some(bits) => {
+                let mut tree = MctsTree::reuse(self.tree.take(), &bits, self.mark);
+                let best = self.grow(&mut tree, rng);
+                self.tree.replace(Some(tree));
+                best
+            }
+            None => self.grow(&mut MctsTree::new(board.current_state.clone(), self.mark), rng),
+        }
+    }
+    fn grow<P: Position + Clone + PartialEq>(&self, tree: &mut MctsTree<P>, rng: &mut GameRng) -> (usize, usize) {
+        let start = Instant::now();
+        let mut iterations = 0;
+        loop {
+            match self.budget {
+                Budget::Iterations(n) if iterations >= n => break,
+                Budget::Time(limit) if iterations > 0 && start.elapsed() >= limit => break,
+                _ => {}
+            }
+            tree.iterate(self.exploration, self.rollout.as_ref(), rng);
+            iterations += 1;
+        }
+        tree.best_move(rng)
+    }
+}
+
+impl Player for MctsPlayer {
+    fn set_mark(&mut self, mark: Marks) {
+        self.mark = mark;
+        self.tree.replace(None);
+    }
+    fn get_mark(&self) -> &Marks {
+        &self.mark
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn choose_move(&self, board: &Board, _q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.search(board, rng)
+    }
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.choose_move(board, q, rng)
+    }
+    fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
+        let (a, b) = *mv;
+        *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BoardSpec;
+    use crate::rng;
+
+    #[test]
+    fn is_mcts_player_working() {
+        let mut player = MctsPlayer::new("MCTS".to_string(), Budget::Iterations(2_000));
+        player.set_mark(Marks::CROSS);
+        let mut rng = rng::seeded(1);
+        let mut board = Board::new();
+        board.current_state[[0, 0]] = 'X';
+        board.current_state[[0, 1]] = 'X';
+        board.current_state[[1, 0]] = '0';
+        board.current_state[[1, 1]] = '0';
+        let mut q = QTable::new();
+        assert_eq!(player.choose_move(&board, &mut q, &mut rng), (0, 2));
+        let mut board = Board::new();
+        board.current_state[[0, 0]] = 'X';
+        board.current_state[[2, 2]] = 'X';
+        board.current_state[[1, 0]] = '0';
+        board.current_state[[1, 1]] = '0';
+        assert_eq!(player.choose_move(&board, &mut q, &mut rng), (1, 2));
+    }
+
+    #[test]
+    fn is_tree_reuse_working() {
+        let mut player = MctsPlayer::new("MCTS".to_string(), Budget::Iterations(500))
+            .with_rollout(Box::new(WinOrBlockRollout));
+        player.set_mark(Marks::CROSS);
+        let mut rng = rng::seeded(2);
+        let mut board = Board::with_spec(BoardSpec::new(5, 5, 4));
+        let mut q = QTable::new();
+        let mv = player.choose_move(&board, &mut q, &mut rng);
+        let tree = player.tree.take().unwrap();
+        let child = tree.root().children.iter().map(|&c| &tree.nodes[c]).find(|n| n.mv == Some(mv));
+        let reply = tree.nodes[child.unwrap().children[0]].mv.unwrap();
+        board.current_state[[mv.0, mv.1]] = 'X';
+        board.current_state[[reply.0, reply.1]] = '0';
+        let expected = board.current_state.to_bitboard().unwrap();
+        let reused = MctsTree::reuse(Some(tree), &expected, Marks::CROSS);
+        assert_eq!(reused.root().position, expected);
+        assert!(reused.root().visits > 0);
+        assert!(reused.nodes.iter().skip(1).all(|n| n.parent.is_some()));
+    }
+
+    #[test]
+    fn is_time_budget_working() {
+        let mut player = MctsPlayer::new("MCTS".to_string(), Budget::Time(Duration::from_millis(50)));
+        player.set_mark(Marks::NOUGHT);
+        let board = Board::with_spec(BoardSpec::new(6, 7, 4));
+        let start = Instant::now();
+        let mv = player.choose_move(&board, &mut QTable::new(), &mut rng::seeded(3));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(board.current_state.available_moves().contains(&mv));
+    }
+
+    #[test]
+    fn is_large_board_mcts_working() {
+        // Too many squares for a bitboard, so the search runs on the char array.
+        let mut player = MctsPlayer::new("MCTS".to_string(), Budget::Iterations(600));
+        player.set_mark(Marks::CROSS);
+        let mut board = Board::with_spec(BoardSpec::new(8, 17, 5));
+        for c in 0..4 {
+            board.current_state[[7, c]] = 'X';
+            board.current_state[[0, 2 * c]] = '0';
+        }
+        let mv = player.choose_move(&board, &mut QTable::new(), &mut rng::seeded(4));
+        assert_eq!(mv, (7, 4));
+        assert!(player.tree.borrow().is_none());
+    }
+}
@@ -0,0 +1,234 @@
+use crate::config::{Hyperparameters, TrainingConfig};
+use crate::players::Marks;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EpisodeStats {
+    pub winner: Option<Marks>,
+    pub winner_name: Option<String>,
+    pub td_error: f32,
+    pub updates: usize,
+    // Summed over updates like `td_error`, for learners that fit a network to a loss.
+    pub loss: f32,
+    // Summed absolute advantage over the moves a policy-gradient step was taken on.
+    pub advantage: f32,
+    pub entropy: f32,
+    pub sampled_moves: usize,
+}
+
+impl EpisodeStats {
+    pub fn record_update(&mut self, td_error: f32) {
+        self.td_error += td_error.abs();
+        self.updates += 1;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsWindow {
+    episodes: usize,
+    cross_wins: usize,
+    nought_wins: usize,
+    draws: usize,
+    rlmax_wins: usize,
+    rlmax_losses: usize,
+    td_error: f32,
+    updates: usize,
+    loss: f32,
+    advantage: f32,
+    entropy: f32,
+    sampled_moves: usize,
+    started: Instant,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricsRecord {
+    pub episode: usize,
+    pub window: usize,
+    pub cross_win_rate: f32,
+    pub nought_win_rate: f32,
+    pub draw_rate: f32,
+    pub rlmax_win_rate: f32,
+    pub rlmax_loss_rate: f32,
+    pub mean_abs_td_error: f32,
+    pub mean_loss: f32,
+    pub mean_advantage: f32,
+    pub mean_entropy: f32,
+    pub q_size: usize,
+    pub epsilon: f32,
+    pub alpha: f32,
+    pub temperature: f32,
+    pub episodes_per_sec: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsFormat {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Debug)]
+pub struct MetricsWriter {
+    writer: BufWriter<File>,
+    format: MetricsFormat,
+}
+
+// Windows the episodes of any trainer and writes a record every `metrics_every` of them.
+#[derive(Debug)]
+pub struct MetricsLog {
+    writer: Option<MetricsWriter>,
+    every: Option<usize>,
+    window: MetricsWindow,
+}
+
+impl Default for MetricsWindow {
+    fn default() -> Self {
+        MetricsWindow {
+            episodes: 0,
+            cross_wins: 0,
+            nought_wins: 0,
+            draws: 0,
+            rlmax_wins: 0,
+            rlmax_losses: 0,
+            td_error: 0.0,
+            updates: 0,
+            loss: 0.0,
+            advantage: 0.0,
+            entropy: 0.0,
+            sampled_moves: 0,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl MetricsWindow {
+    pub fn record(&mut self, stats: &EpisodeStats) {
+        self.episodes += 1;
+        match stats.winner {
+            Some(Marks::CROSS) => self.cross_wins += 1,
+            Some(_) => self.nought_wins += 1,
+            None => self.draws += 1,
+        }
+        match stats.winner_name.as_deref() {
+            Some("RLmax") => self.rlmax_wins += 1,
+            Some(_) => self.rlmax_losses += 1,
+            None => {}
+        }
+        self.td_error += stats.td_error;
+        self.updates += stats.updates;
+        self.loss += stats.loss;
+        self.advantage += stats.advantage;
+        self.entropy += stats.entropy;
+        self.sampled_moves += stats.sampled_moves;
+    }
+
+    // Turns the window into a record and starts a new one.
+    pub fn flush(&mut self, episode: usize, q_size: usize, params: &Hyperparameters) -> MetricsRecord {
+        let window = std::mem::take(self);
+        let n = window.episodes.max(1) as f32;
+        MetricsRecord {
+            episode,
+            window: window.episodes,
+            cross_win_rate: window.cross_wins as f32 / n,
+            nought_win_rate: window.nought_wins as f32 / n,
+            draw_rate: window.draws as f32 / n,
+            rlmax_win_rate: window.rlmax_wins as f32 / n,
+            rlmax_loss_rate: window.rlmax_losses as f32 / n,
+            mean_abs_td_error: window.td_error / window.updates.max(1) as f32,
+            mean_loss: window.loss / window.updates.max(1) as f32,
+            mean_advantage: window.advantage / window.updates.max(1) as f32,
+            mean_entropy: window.entropy / window.sampled_moves.max(1) as f32,
+            q_size,
+            epsilon: params.epsilon,
+            alpha: params.alpha,
+            temperature: params.temperature,
+            episodes_per_sec: window.episodes as f32 / window.started.elapsed().as_secs_f32(),
+        }
+    }
+}
+
+impl MetricsRecord {
+    pub const CSV_HEADER: &'static str = "episode,window,cross_win_rate,nought_win_rate,draw_rate,rlmax_win_rate,rlmax_loss_rate,mean_abs_td_error,mean_loss,mean_advantage,mean_entropy,q_size,epsilon,alpha,temperature,episodes_per_sec";
+
+    pub fn to_csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.episode,
+            self.window,
+            self.cross_win_rate,
+            self.nought_win_rate,
+            self.draw_rate,
+            self.rlmax_win_rate,
+            self.rlmax_loss_rate,
+            self.mean_abs_td_error,
+            self.mean_loss,
+            self.mean_advantage,
+            self.mean_entropy,
+            self.q_size,
+            self.epsilon,
+            self.alpha,
+            self.temperature,
+            self.episodes_per_sec,
+        )
+    }
+}
+
+impl MetricsWriter {
+    // A fresh run starts the file over; a resumed one appends so it continues the same learning curve.
+    pub fn open(path: &Path, resume: bool) -> Result<Self, anyhow::Error> {
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => MetricsFormat::Csv,
+            _ => MetricsFormat::JsonLines,
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(path)?;
+        let is_empty = file.metadata()?.len() == 0;
+        let mut writer = BufWriter::new(file);
+        if format == MetricsFormat::Csv && is_empty {
+            writeln!(writer, "{}", MetricsRecord::CSV_HEADER)?;
+        }
+        Ok(MetricsWriter { writer, format })
+    }
+    pub fn write(&mut self, record: &MetricsRecord) -> Result<(), anyhow::Error> {
+        match self.format {
+            MetricsFormat::Csv => writeln!(self.writer, "{}", record.to_csv_row())?,
+            MetricsFormat::JsonLines => writeln!(self.writer, "{}", serde_json::to_string(record)?)?,
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl MetricsLog {
+    pub fn open(config: &TrainingConfig, resume: bool) -> Self {
+        let writer = config.metrics_every.and_then(|_| {
+            MetricsWriter::open(&config.metrics_path, resume)
+                .inspect_err(|e| println!("Could not open the metrics file: {e}"))
+                .ok()
+        });
+        MetricsLog {
+            writer,
+            every: config.metrics_every,
+            window: MetricsWindow::default(),
+        }
+    }
+    // `episode` counts the episodes played so far, this one included.
+    pub fn record(&mut self, episode: usize, stats: &EpisodeStats, q_size: usize, params: &Hyperparameters) {
+        self.window.record(stats);
+        if let Some(writer) = self.writer.as_mut() {
+            if self.every.is_some_and(|every| episode.is_multiple_of(every)) {
+                let record = self.window.flush(episode, q_size, params);
+                if let Err(e) = writer.write(&record) {
+                    println!("Could not write the metrics: {e}");
+                }
+            }
+        }
+    }
+}
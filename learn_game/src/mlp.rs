@@ -0,0 +1,114 @@
+use crate::rng::GameRng;
+use ndarray::{Array1, Array2, Axis};
+use rand::Rng;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    // One row per output unit.
+    pub weights: Array2<f32>,
+    pub bias: Array1<f32>,
+}
+
+// Fully connected network with ReLU hidden layers and a linear output layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mlp {
+    pub layers: Vec<Layer>,
+}
+
+impl Mlp {
+    // He initialisation, suited to ReLU units.
+    pub fn new(sizes: &[usize], rng: &mut GameRng) -> Self {
+        let layers = sizes
+            .windows(2)
+            .map(|pair| {
+                let (inputs, outputs) = (pair[0], pair[1]);
+                let limit = (6.0 / inputs as f32).sqrt();
+                Layer {
+                    weights: Array2::from_shape_fn((outputs, inputs), |_| rng.gen_range(-limit..limit)),
+                    bias: Array1::zeros(outputs),
+                }
+            })
+            .collect();
+        Mlp { layers }
+    }
+    pub fn forward(&self, x: &Array1<f32>) -> Array1<f32> {
+        let batch = x.view().insert_axis(Axis(0)).to_owned();
+        self.activations(&batch).pop().unwrap().index_axis_move(Axis(0), 0)
+    }
+    // The input and every layer's output for a batch with one example per row.
+    pub fn activations(&self, inputs: &Array2<f32>) -> Vec<Array2<f32>> {
+        let mut outputs = vec![inputs.clone()];
+        for (i, layer) in self.layers.iter().enumerate() {
+            let mut z = outputs.last().unwrap().dot(&layer.weights.t()) + &layer.bias;
+            if i + 1 < self.layers.len() {
+                z.mapv_inplace(|v| v.max(0.0));
+            }
+            outputs.push(z);
+        }
+        outputs
+    }
+    // Backpropagates the loss gradient at the output, already averaged over the batch, and takes an SGD step.
+    pub fn backward(&mut self, activations: &[Array2<f32>], mut delta: Array2<f32>, learning_rate: f32) {
+        for i in (0..self.layers.len()).rev() {
+            let input = &activations[i];
+            let grad_weights = delta.t().dot(input);
+            let grad_bias = delta.sum_axis(Axis(0));
+            if i > 0 {
+                let mut previous = delta.dot(&self.layers[i].weights);
+                previous.zip_mut_with(input, |d, &a| {
+                    if a <= 0.0 {
+                        *d = 0.0
+                    }
+                });
+                delta = previous;
+            }
+            let layer = &mut self.layers[i];
+            layer.weights.scaled_add(-learning_rate, &grad_weights);
+            layer.bias.scaled_add(-learning_rate, &grad_bias);
+        }
+    }
+    // One SGD step on the Huber loss between the chosen outputs and their targets.
+    // Returns the mean absolute error before the step and the mean loss.
+    pub fn train_batch(&mut self, inputs: &Array2<f32>, actions: &[usize], targets: &Array1<f32>, learning_rate: f32) -> (f32, f32) {
+        let activations = self.activations(inputs);
+        let output = activations.last().unwrap();
+        let n = inputs.nrows() as f32;
+        let mut delta = Array2::zeros(output.raw_dim());
+        let (mut abs_error, mut loss) = (0.0, 0.0);
+        for (row, (&action, &target)) in actions.iter().zip(targets.iter()).enumerate() {
+            let error = output[[row, action]] - target;
+            abs_error += error.abs();
+            loss += if error.abs() <= 1.0 {
+                0.5 * error * error
+            } else {
+                error.abs() - 0.5
+            };
+            delta[[row, action]] = error.clamp(-1.0, 1.0) / n;
+        }
+        self.backward(&activations, delta, learning_rate);
+        (abs_error / n, loss / n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng;
+
+    #[test]
+    fn is_mlp_working() {
+        let mut rng = rng::seeded(0);
+        let mut net = Mlp::new(&[4, 8, 2], &mut rng);
+        let inputs = Array2::from_shape_vec((2, 4), vec![1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0]).unwrap();
+        let targets = Array1::from(vec![0.5, -0.5]);
+        let (error, first) = net.train_batch(&inputs, &[0, 1], &targets, 0.05);
+        // The Huber loss never exceeds the absolute error.
+        assert!(first > 0.0 && first <= error);
+        let mut last = first;
+        for _ in 0..200 {
+            (_, last) = net.train_batch(&inputs, &[0, 1], &targets, 0.05);
+        }
+        assert!(last < first * 0.01);
+        assert!((net.forward(&inputs.row(0).to_owned())[0] - 0.5).abs() < 0.05);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::config::{Hyperparameters, VisitKind};
+use crate::q_table::{Outcome, QTable, Transition};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub key: String,
+    pub mv: (usize, usize),
+    pub player: String,
+}
+
+// The moves of one episode, replayed backwards once the final reward is known.
+#[derive(Debug, Clone)]
+pub struct Trajectory {
+    pub kind: VisitKind,
+    pub steps: Vec<Step>,
+}
+
+impl Trajectory {
+    pub fn new(kind: VisitKind) -> Self {
+        Trajectory {
+            kind,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, key: &str, mv: &(usize, usize), player: &str) {
+        self.steps.push(Step {
+            key: key.to_owned(),
+            mv: *mv,
+            player: player.to_owned(),
+        });
+    }
+
+    // Moves every visited pair towards the discounted return from that point on, and returns the errors.
+    // Values are kept from RLmax's point of view, so the same return serves both players.
+    pub fn update(&self, q: &mut QTable, reward: f32, params: &Hyperparameters) -> Vec<f32> {
+        let mut td_errors = Vec::new();
+        let mut ret = reward;
+        for (i, step) in self.steps.iter().enumerate().rev() {
+            let is_first_visit = !self.steps[..i]
+                .iter()
+                .any(|earlier| earlier.key == step.key && earlier.mv == step.mv);
+            if self.kind == VisitKind::EveryVisit || is_first_visit {
+                td_errors.push(q.update_toward(&step.key, &step.mv, ret, params));
+            }
+            ret *= params.gamma;
+        }
+        td_errors
+    }
+
+    // Records the move, and once the game is over replays the episode towards its returns.
+    pub fn learn(&mut self, q: &mut QTable, t: &Transition, params: &Hyperparameters) -> Vec<f32> {
+        self.record(t.key, &t.mv, t.player);
+        match t.outcome {
+            Outcome::InPlay(_) => Vec::new(),
+            Outcome::Over(reward) => self.update(q, reward, params),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Algorithm, TrainingConfig};
+    use crate::q_table::Moves;
+    use crate::tests::seeded_game;
+
+    #[test]
+    fn is_trajectory_working() {
+        let params = Hyperparameters {
+            alpha: 0.5,
+            gamma: 0.5,
+            ..Hyperparameters::default()
+        };
+        let new_q = || {
+            let mut q = QTable::new();
+            for key in ["first", "second"] {
+                let mut moves = Moves::from(vec![(0, 0)]);
+                moves.insert((0, 0), 0.0);
+                q.insert(key.to_string(), moves);
+            }
+            q
+        };
+        let mut every = Trajectory::new(VisitKind::EveryVisit);
+        every.record("first", &(0, 0), "RLmax");
+        every.record("second", &(0, 0), "RLmin");
+        every.record("first", &(0, 0), "RLmax");
+        let mut first = every.clone();
+        first.kind = VisitKind::FirstVisit;
+
+        // Returns are 0.25, 0.5 and 1.0; every-visit updates "first" twice.
+        let mut q = new_q();
+        assert_eq!(every.update(&mut q, 1.0, &params), vec![1.0, 0.5, -0.25]);
+        assert_eq!(q.get("first").unwrap()[&(0, 0)], 0.375);
+        assert_eq!(q.get("second").unwrap()[&(0, 0)], 0.25);
+
+        let mut q = new_q();
+        assert_eq!(first.update(&mut q, 1.0, &params), vec![0.5, 0.25]);
+        assert_eq!(q.get("first").unwrap()[&(0, 0)], 0.125);
+        assert_eq!(q.get("second").unwrap()[&(0, 0)], 0.25);
+
+        first.clear();
+        assert!(first.steps.is_empty());
+    }
+
+    #[test]
+    fn is_monte_carlo_working() {
+        let (mut game, mut q) = seeded_game(TrainingConfig {
+            seed: Some(6),
+            algorithm: Algorithm::MonteCarlo,
+            ..TrainingConfig::default()
+        });
+        for _ in 0..300 {
+            let stats = game.learn_episode(Some(&mut q));
+            // Every move of the episode is updated once the game is over.
+            let played = game.spec.cells() - game.board.next_state.available_moves().len();
+            assert_eq!(stats.updates, played);
+        }
+    }
+}
@@ -1,17 +1,21 @@
-use crate::board::{Board, IsGameOver, GameState};
-use crate::config::{EXPLORATION_RATE, K};
-use crate::q_table::{QTable, Moves};
+use crate::board::{Board, BoardSpec, GameState};
+use crate::config::{Exploration, Hyperparameters};
+use crate::mcts::{RolloutPolicy, WinOrBlockRollout};
+use crate::q_table::{Moves, QTable};
+use crate::rng::GameRng;
+use crate::search::{AlphaBeta, Position};
+use crate::solver;
 use rand::prelude::SliceRandom;
-use rand::{thread_rng, Rng};
-use std::ops::DerefMut;
-use std::io;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use chrono::Local;
+use std::cell::{Cell, RefCell};
 use std::fs::File;
-use std::path::{Path, PathBuf};
+use std::io;
 use std::io::Read;
-use std::cell::RefCell;
+use std::path::{Path, PathBuf};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum Marks {
     CROSS = 88isize,
     NOUGHT = 48isize,
@@ -28,28 +32,63 @@ pub struct HumanPlayer {
 pub struct ComputerPlayerRLmax {
     pub name: String,
     pub mark: Marks,
+    pub params: Hyperparameters,
+    pub last_entropy: Cell<Option<f32>>,
 }
 
 #[derive(Debug)]
 pub struct ComputerPlayerRLmin {
     pub name: String,
     pub mark: Marks,
+    pub params: Hyperparameters,
+    pub last_entropy: Cell<Option<f32>>,
 }
 #[derive(Debug)]
 pub struct MinimaxPlayer {
     pub name: String,
     pub mark: Marks,
     pub q_max: RefCell<QTable>,
+    pub search: RefCell<AlphaBeta>,
+}
+
+// Plays greedily from its own frozen copy of a table, as the side that minimizes; an empty table plays at random.
+#[derive(Debug)]
+pub struct SnapshotPlayer {
+    pub name: String,
+    pub mark: Marks,
+    pub q: QTable,
+}
+
+// Wins when it can, blocks when it must, and otherwise plays at random.
+#[derive(Debug)]
+pub struct HeuristicPlayer {
+    pub name: String,
+    pub mark: Marks,
+}
+
+// Picks uniformly among the free squares.
+#[derive(Debug)]
+pub struct RandomPlayer {
+    pub name: String,
+    pub mark: Marks,
 }
 
 pub trait Player {
     fn set_mark(&mut self, mark: Marks);
     fn get_mark(&self) -> &Marks;
     fn get_name(&self) -> &str;
-    fn choose_move(&self, board: &Board, q: &mut QTable) -> (usize, usize);
+    fn choose_move(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize);
     fn make_move(&self, board: &mut Board, mv: &(usize, usize));
-    fn choose_move_k(&self, board: &Board, q: &mut QTable) -> (usize, usize);
-    fn q_to_disk(&mut self) -> Result<(), anyhow::Error>;
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize);
+    // Only players that keep a table of their own have one to save.
+    fn q_to_disk(&mut self) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!("{} has no table to save.", self.get_name()))
+    }
+    fn set_hyperparameters(&mut self, _params: &Hyperparameters) {}
+    // Entropy of the distribution behind the last sampled move, cleared once read.
+    fn last_entropy(&self) -> Option<f32> {
+        None
+    }
 }
 
 impl Marks {
@@ -78,62 +117,54 @@ impl Player for HumanPlayer {
     fn get_name(&self) -> &str {
         &self.name
     }
-    fn choose_move(&self, board: &Board, q: &mut QTable) -> (usize, usize) {
-        fn move_from_human(board: &Board, _q: &QTable, name: &str) -> Result<(usize, usize), io::Error> {
-            let mut row_buf = [0u8; 2];
-            let mut col_buf = [0u8; 2];
-            let mut garbage = String::new();
-            println!("Drawing current state from human.choose_move()");
-            board.current_state.draw();
-            println!("{:?}, please, choose your move", name);
-            println!("please, enter the row number (1-3):");
+    fn choose_move(&self, board: &Board, q: &mut QTable, _rng: &mut GameRng) -> (usize, usize) {
+        fn read_index(what: &str, size: usize) -> Result<usize, io::Error> {
+            println!("please, enter the {what} number (1-{size}):");
             let mut counter = 0_i32;
             loop {
-                if counter > 2 {println!("You tried 3 times"); return Err(io::Error::other("Wrong symbol 3 times"));}
-                io::stdin().read_exact(&mut row_buf)?;
-                if (49..=51).contains(&row_buf[0]) {
-                    break;
-                } else {
-                    println!("Unknown symbol, please, try again (a number 1, 2 or 3):");
-                    io::stdin().read_line(&mut garbage);
-                    row_buf = [0u8; 2];
+                if counter > 2 {
+                    println!("You tried 3 times");
+                    return Err(io::Error::other("Wrong symbol 3 times"));
                 }
-                counter += 1;
-            }
-            println!("please, enter the column number (1-3):");
-            let mut counter = 0_i32;
-            loop {
-                if counter > 2 {println!("You tried 3 times"); return Err(io::Error::other("Wrong symbol 3 times"));}
-                io::stdin().read_exact(&mut col_buf)?;
-                if (49..=51).contains(&col_buf[0]) {
-                    break;
-                } else {
-                    println!("Unknown symbol, please, try again (a number 1, 2 or 3):");
-                    io::stdin().read_line(&mut garbage);
-                    col_buf = [0u8; 2];
+                let mut buf = String::new();
+                if io::stdin().read_line(&mut buf)? == 0 {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+                }
+                match buf.trim().parse::<usize>() {
+                    Ok(n) if (1..=size).contains(&n) => return Ok(n - 1),
+                    _ => println!("Unknown symbol, please, try again (a number from 1 to {size}):"),
                 }
                 counter += 1;
             }
-            let r = char::from(row_buf[0]).to_digit(10).unwrap();
-            let c = char::from(col_buf[0]).to_digit(10).unwrap();
-            let mv = (r as usize - 1, c as usize - 1);
-            if board.current_state.available_moves().contains(&mv) {return Ok(mv)
-            } else {return Err(io::Error::other("The square is taken, please, choose another one."))}
         }
-        loop{
-            if let Ok((x, y)) = move_from_human(board, q, self.get_name()) {return (x, y);
-            } else {println!("Please, try choosing your move again.")}
+        fn move_from_human(board: &Board, _q: &QTable, name: &str) -> Result<(usize, usize), io::Error> {
+            println!("Drawing current state from human.choose_move()");
+            board.current_state.draw();
+            println!("{:?}, please, choose your move", name);
+            let spec = board.spec();
+            let mv = (read_index("row", spec.rows)?, read_index("column", spec.cols)?);
+            if board.current_state.available_moves().contains(&mv) {
+                Ok(mv)
+            } else {
+                Err(io::Error::other("The square is taken, please, choose another one."))
+            }
+        }
+        loop {
+            match move_from_human(board, q, self.get_name()) {
+                Ok((x, y)) => return (x, y),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    panic!("The input was closed before {} chose a move.", self.get_name())
+                }
+                Err(_) => println!("Please, try choosing your move again."),
+            }
         }
     }
 
-    fn choose_move_k(&self, board: &Board, q: &mut QTable) -> (usize, usize){
-        unimplemented!()
-    }
-    fn q_to_disk(&mut self) -> Result<(), anyhow::Error> {
+    fn choose_move_k(&self, _board: &Board, _q: &mut QTable, _rng: &mut GameRng) -> (usize, usize) {
         unimplemented!()
     }
     fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
-        let (a, b) = mv.clone();
+        let (a, b) = *mv;
         println!("Inside make move a {:?} b {:?}", a, b);
         *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
     }
@@ -165,7 +196,7 @@ impl HumanPlayer {
         println!("Human player, do you want to play X (makes the first move) or 0?");
         io::stdin().read_exact(&mut mark_byte[..])?;
         if mark_byte[1] != 10 {
-            io::stdin().read_line(&mut garbage_mark);
+            io::stdin().read_line(&mut garbage_mark)?;
         }
         Ok(mark_byte[0] as char)
     }
@@ -181,45 +212,24 @@ impl Player for ComputerPlayerRLmax {
     fn get_name(&self) -> &str {
         &self.name
     }
-    fn choose_move(&self, board: &Board, q: &mut QTable) -> (usize, usize) {
-        let current_state_key = board.current_state.to_state_key() + self.get_name();
-        let mut rng = thread_rng();
-        let n = rng.gen_range(0_f32..=1_f32);
-        EXPLORATION_RATE.with_borrow(|erate| {
-            if !q.contains_key(&current_state_key) || n < *erate {
-                let available_moves = board.current_state.available_moves();
-                return *available_moves.choose(&mut rng).unwrap();
-            } else {
-                let state_moves = q.get(&current_state_key).unwrap();
-                return *state_moves.select_max_move();
-            }
-        })
+    fn choose_move(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        explore(board, q, self.get_name(), &self.params, false, rng)
     }
     fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
         let (a, b) = *mv;
         *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
     }
-    fn choose_move_k(&self, board: &Board, q: &mut QTable) -> (usize, usize) {
-        let current_state_key = board.current_state.to_state_key() + self.get_name();
-        let mut moves_with_probabilities: Moves = q.get(&current_state_key).unwrap().clone();
-        let min_val = moves_with_probabilities.values().min_by(|a, b| a.total_cmp(b)).unwrap();
-        if *min_val < 0_f32 {
-            let constant = min_val.abs() + 0.1;
-            for value in moves_with_probabilities.values_mut() {
-                *value += constant;
-            }
-        }
-        K.with_borrow(|k| {
-            let sum = moves_with_probabilities.values().fold(0_f32, |acc, x| acc + x.powf(*k));
-            for value in moves_with_probabilities.values_mut() {
-                *value = value.powf(*k) / sum;
-            }
-        });
-        let max_move = moves_with_probabilities.iter().max_by(|&x, &y| x.1.total_cmp(y.1)).map(|(key, _value)| key);
-        max_move.unwrap().clone()
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        let key = q.state_key(&board.current_state, self.get_name());
+        let (mv, entropy) = q.get(&key.key).unwrap().sample_softmax(self.params.temperature, false, rng);
+        self.last_entropy.set(Some(entropy));
+        key.from_table(&mv)
     }
-    fn q_to_disk(&mut self) -> Result<(), anyhow::Error> {
-        unimplemented!()
+    fn set_hyperparameters(&mut self, params: &Hyperparameters) {
+        self.params = *params;
+    }
+    fn last_entropy(&self) -> Option<f32> {
+        self.last_entropy.take()
     }
 }
 
@@ -233,47 +243,83 @@ impl Player for ComputerPlayerRLmin {
     fn get_name(&self) -> &str {
         &self.name
     }
-    fn choose_move(&self, board: &Board, q: &mut QTable) -> (usize, usize) {
-        let current_state_key = board.current_state.to_state_key() + self.get_name();
-        let mut rng = thread_rng();
-        let n = rng.gen_range(0f32..=1f32);
-        EXPLORATION_RATE.with_borrow(|erate| {
-            if !q.contains_key(&current_state_key) || n < *erate {
-                let available_moves = board.current_state.available_moves();
-                return *available_moves.choose(&mut rng).unwrap();
-            } else {
-                return *q.get(&current_state_key).unwrap().select_min_move();
-            }
-        })
+    fn choose_move(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        explore(board, q, self.get_name(), &self.params, true, rng)
     }
     fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
         let (a, b) = *mv;
         *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
     }
-    fn choose_move_k(&self, board: &Board, q: &mut QTable) -> (usize, usize) {
-        let current_state_key = board.current_state.to_state_key() + self.get_name();
-        let mut moves_with_probabilities: Moves = q.get(&current_state_key).unwrap().clone();
-        let min_val = moves_with_probabilities.values().min_by(|a, b| a.total_cmp(b)).unwrap();
-        if *min_val < 0_f32 {
-            let constant = min_val.abs() + 0.1;
-            for value in moves_with_probabilities.values_mut() {
-                *value += constant;
-            }
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        let key = q.state_key(&board.current_state, self.get_name());
+        let (mv, entropy) = q.get(&key.key).unwrap().sample_softmax(self.params.temperature, true, rng);
+        self.last_entropy.set(Some(entropy));
+        key.from_table(&mv)
+    }
+    fn set_hyperparameters(&mut self, params: &Hyperparameters) {
+        self.params = *params;
+    }
+    fn last_entropy(&self) -> Option<f32> {
+        self.last_entropy.take()
+    }
+}
+
+impl ComputerPlayerRLmax {
+    pub fn new(name: String) -> Self {
+        ComputerPlayerRLmax {
+            name,
+            mark: Marks::None,
+            params: Hyperparameters::default(),
+            last_entropy: Cell::new(None),
         }
-        K.with_borrow(|k| {
-            let sum = moves_with_probabilities.values().fold(0_f32, |acc, x| acc + x.powf(*k));
-            for value in moves_with_probabilities.values_mut() {
-                *value = value.powf(*k) / sum;
-            }
-        });
-        let min_move = moves_with_probabilities.iter().min_by(|&x, &y| x.1.total_cmp(y.1)).map(|(key, _value)| key);
-        min_move.unwrap().clone()
     }
-    fn q_to_disk(&mut self) -> Result<(), anyhow::Error> {
-        unimplemented!()
+}
+
+impl ComputerPlayerRLmin {
+    pub fn new(name: String) -> Self {
+        ComputerPlayerRLmin {
+            name,
+            mark: Marks::None,
+            params: Hyperparameters::default(),
+            last_entropy: Cell::new(None),
+        }
     }
 }
 
+// Move choice shared by the RL players under the configured exploration strategy.
+fn explore(
+    board: &Board,
+    q: &QTable,
+    name: &str,
+    params: &Hyperparameters,
+    minimize: bool,
+    rng: &mut GameRng,
+) -> (usize, usize) {
+    let key = q.state_key(&board.current_state, name);
+    let n = rng.gen_range(0_f32..=1_f32);
+    let greedy = |moves: &Moves, rng: &mut GameRng| {
+        if minimize {
+            *moves.select_min_move(rng)
+        } else {
+            *moves.select_max_move(rng)
+        }
+    };
+    let table_move = match (q.get(&key.key), params.exploration) {
+        (Some(moves), Exploration::EpsilonGreedy) if n >= params.epsilon => greedy(moves, rng),
+        (Some(moves), Exploration::Optimistic { .. }) => greedy(moves, rng),
+        (Some(moves), Exploration::Ucb { c }) => *moves.select_ucb_move(c, minimize, rng),
+        (Some(moves), Exploration::CountBased { c }) => {
+            if n < params.epsilon * c / (c + moves.total_visits() as f32) {
+                *moves.select_least_visited_move(rng)
+            } else {
+                greedy(moves, rng)
+            }
+        }
+        _ => return *board.current_state.available_moves().choose(rng).unwrap(),
+    };
+    key.from_table(&table_move)
+}
+
 impl Player for MinimaxPlayer {
     fn set_mark(&mut self, mark: Marks) {
         self.mark = mark;
@@ -284,25 +330,21 @@ impl Player for MinimaxPlayer {
     fn get_name(&self) -> &str {
         &self.name
     }
-    fn choose_move(&self, board: &Board, q: &mut QTable) -> (usize, usize) {
+    fn choose_move(&self, board: &Board, _q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
         let current_state_key = board.current_state.to_state_key() + "max";
         if let Some(mvs) = self.q_max.borrow_mut().get(&current_state_key) {
-            return *mvs.select_max_move()
+            return *mvs.select_max_move(rng);
         }
-        let available_moves = board.current_state.available_moves();
-        let mut moves_map: Moves = Moves::from(available_moves);
-        moves_map.iter_mut().for_each(|(mv, val)| {
-            let mut state: GameState = board.current_state.clone();
-            let (a, b) = *mv;
-            *state.get_mut([a, b]).unwrap() = self.mark.as_char();
-            *val = Self::minimax(state, &self.get_mark().other(), &0, false) as f32;
-        });
-        let best = *moves_map.select_max_move();
+        let moves_map: Moves = self
+            .search
+            .borrow_mut()
+            .evaluate_moves(&board.current_state, &self.mark);
+        let best = *moves_map.select_max_move(rng);
         self.q_max.borrow_mut().insert(current_state_key, moves_map);
         best
     }
-    fn choose_move_k(&self, board: &Board, q: &mut QTable) -> (usize, usize){
-        self.choose_move(board, q)
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.choose_move(board, q, rng)
     }
     fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
         let (a, b) = *mv;
@@ -313,50 +355,161 @@ impl Player for MinimaxPlayer {
         let today = dt.date_naive();
         let filename = "qtable-max-".to_owned() + (&today.to_string()) + r#".pickle"#;
         let path = Path::new("./q_table_archive/");
-        let q_pickle: PathBuf = [path, &Path::new(&filename)].iter().collect();
+        let q_pickle: PathBuf = [path, Path::new(&filename)].iter().collect();
         let mut file = File::create(&q_pickle)?;
         serde_pickle::to_writer(&mut file, self.q_max.get_mut(), serde_pickle::SerOptions::new())?;
         Ok(())
     }
 }
 impl MinimaxPlayer {
-    pub fn minimax (mut state: GameState, mark: &Marks, depth: &i32, isMax: bool) -> i32 {
-        match state.is_game_over(&mark.other()) {
-            IsGameOver::Win if isMax => {
-                return -10 + depth},
-            IsGameOver::Win if !isMax => {
-                return 10 - depth},
-            IsGameOver::Drawn => {
-                return 0},
-            IsGameOver::InPlay => {
-                if isMax {
-                    let mut available_moves: Moves = Moves::from(state.available_moves());
-                    available_moves.iter_mut().for_each(|(mv, val)| {
-                        let (a, b) = *mv;
-                        *state.get_mut([a, b]).unwrap() = mark.as_char();
-                        *val = Self::minimax(state.clone(), &mark.other(), &(depth + 1), !isMax) as f32;
-                        *state.get_mut([a, b]).unwrap() = '-';
-                    });
-                    available_moves.values().max_by(|x, y| x.total_cmp(y)).unwrap().clone() as i32
-                } else {
-                    let mut available_moves: Moves = Moves::from(state.available_moves());
-                    available_moves.iter_mut().for_each(|(mv, val)| {
-                        let (a, b) = *mv;
-                        *state.get_mut([a, b]).unwrap() = mark.as_char();
-                        *val = Self::minimax(state.clone(), &mark.other(), &(depth + 1), !isMax) as f32;
-                        *state.get_mut([a, b]).unwrap() = '-';
-                    });
-                    available_moves.values().min_by(|x, y| x.total_cmp(y)).unwrap().clone() as i32
-                }
-            },
-            IsGameOver => unreachable!(),
+    pub fn new(name: String, q_max: QTable) -> Self {
+        MinimaxPlayer {
+            name,
+            mark: Marks::None,
+            q_max: RefCell::new(q_max),
+            search: RefCell::new(AlphaBeta::new(None)),
+        }
+    }
+    pub fn perfect(name: String, spec: BoardSpec) -> Self {
+        Self::new(name, solver::solve(spec).to_q_table())
+    }
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.search.get_mut().max_depth = Some(max_depth);
+        self
+    }
+    pub fn minimax(mut state: GameState, mark: &Marks, depth: &i32, is_max: bool) -> i32 {
+        match state.to_bitboard() {
+            Some(mut bits) => Self::minimax_in(&mut bits, mark, *depth, is_max),
+            None => Self::minimax_in(&mut state, mark, *depth, is_max),
+        }
+    }
+    pub fn minimax_in<P: Position>(position: &mut P, mark: &Marks, depth: i32, is_max: bool) -> i32 {
+        let win = position.spec().cells() as i32 + 1;
+        if position.is_win(&mark.other()) {
+            return if is_max { -win + depth } else { win - depth };
+        }
+        if position.is_full() {
+            return 0;
+        }
+        let values = position.moves().into_iter().map(|mv| {
+            position.play(&mv, mark);
+            let value = Self::minimax_in(position, &mark.other(), depth + 1, !is_max);
+            position.undo(&mv);
+            value
+        });
+        if is_max {
+            values.max().unwrap()
+        } else {
+            values.min().unwrap()
+        }
+    }
+}
+
+impl Player for SnapshotPlayer {
+    fn set_mark(&mut self, mark: Marks) {
+        self.mark = mark;
+    }
+    fn get_mark(&self) -> &Marks {
+        &self.mark
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn choose_move(&self, board: &Board, _q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        explore(board, &self.q, "RLmin", &Hyperparameters::greedy(), true, rng)
+    }
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.choose_move(board, q, rng)
+    }
+    fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
+        let (a, b) = *mv;
+        *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
+    }
+}
+
+impl SnapshotPlayer {
+    pub fn new(name: String, q: QTable) -> Self {
+        SnapshotPlayer {
+            name,
+            mark: Marks::None,
+            q,
+        }
+    }
+}
+
+impl Player for HeuristicPlayer {
+    fn set_mark(&mut self, mark: Marks) {
+        self.mark = mark;
+    }
+    fn get_mark(&self) -> &Marks {
+        &self.mark
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn choose_move(&self, board: &Board, _q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        match board.current_state.to_bitboard() {
+            Some(mut bits) => WinOrBlockRollout.choose(&mut bits, &self.mark, rng),
+            None => WinOrBlockRollout.choose(&mut board.current_state.clone(), &self.mark, rng),
+        }
+    }
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.choose_move(board, q, rng)
+    }
+    fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
+        let (a, b) = *mv;
+        *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
+    }
+}
+
+impl HeuristicPlayer {
+    pub fn new(name: String) -> Self {
+        HeuristicPlayer {
+            name,
+            mark: Marks::None,
         }
     }
 }
 
+impl Player for RandomPlayer {
+    fn set_mark(&mut self, mark: Marks) {
+        self.mark = mark;
+    }
+    fn get_mark(&self) -> &Marks {
+        &self.mark
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn choose_move(&self, board: &Board, _q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        Self::random_move(board, rng)
+    }
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.choose_move(board, q, rng)
+    }
+    fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
+        let (a, b) = *mv;
+        *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
+    }
+}
+
+impl RandomPlayer {
+    pub fn new(name: String) -> Self {
+        RandomPlayer {
+            name,
+            mark: Marks::None,
+        }
+    }
+    // Also what the environment's random opponent plays.
+    pub fn random_move(board: &Board, rng: &mut GameRng) -> (usize, usize) {
+        *board.current_state.available_moves().choose(rng).unwrap()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rng;
     #[test]
     fn is_minimax_working() {
         let mut state: GameState = GameState::new();
@@ -373,6 +526,39 @@ mod tests {
         println!("The value is {:?}", value);
     }
     #[test]
+    fn is_large_board_minimax_working() {
+        // Too many squares for a bitboard; pairs of marks that never make five in a row, two squares left.
+        let mut state = GameState::with_spec(BoardSpec::new(8, 17, 5));
+        for ((r, c), value) in state.indexed_iter_mut() {
+            *value = if (c + 2 * r) % 4 < 2 { 'X' } else { '0' };
+        }
+        state[[0, 0]] = '-';
+        state[[0, 2]] = '-';
+        assert!(state.to_bitboard().is_none());
+        assert_eq!(MinimaxPlayer::minimax(state, &Marks::CROSS, &0, true), 0);
+    }
+    #[test]
+    fn is_heuristic_player_working() {
+        let mut heuristic = HeuristicPlayer::new("heuristic".to_string());
+        heuristic.set_mark(Marks::NOUGHT);
+        let mut board = Board::new();
+        board.current_state[[0, 0]] = 'X';
+        board.current_state[[0, 1]] = 'X';
+        let mut rng = rng::seeded(0);
+        // Blocks the row X is about to complete.
+        assert_eq!(heuristic.choose_move(&board, &mut QTable::new(), &mut rng), (0, 2));
+    }
+    #[test]
+    fn is_random_player_working() {
+        let mut board = Board::new();
+        board.current_state.fill('X');
+        board.current_state[[2, 1]] = '-';
+        let player = RandomPlayer::new("random".to_string());
+        let mut rng = rng::seeded(0);
+        assert_eq!(player.choose_move(&board, &mut QTable::new(), &mut rng), (2, 1));
+        assert_eq!(RandomPlayer::random_move(&board, &mut rng), (2, 1));
+    }
+    #[test]
     fn is_marks_working() {
         let mark_1: Marks = Marks::CROSS;
         let mark_2: Marks = mark_1.other();
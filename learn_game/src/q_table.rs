@@ -1,12 +1,14 @@
-use crate::board::Board;
-use crate::config::{DISCOUNT_RATE, LEARNING_RATE};
+use crate::board::{Board, BoardSpec, GameState};
+use crate::config::Hyperparameters;
+use crate::rng::GameRng;
+use crate::symmetry::Symmetry;
 use chrono::offset::Local;
 use itertools::Itertools;
-use rand::{prelude::SliceRandom, thread_rng, Rng};
-use serde::de::{Deserializer, MapAccess, Visitor};
+use rand::{prelude::SliceRandom, Rng};
+use serde::de::{self, Deserializer, MapAccess, Visitor};
 use serde::ser::{SerializeMap, Serializer};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{prelude::*, BufReader};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
@@ -17,16 +19,56 @@ use std::{fmt, fs::File};
 pub struct Moves {
     #[serde(serialize_with = "serialize_moves")]
     #[serde(deserialize_with = "deserialize_moves")]
-    pub moves: HashMap<(usize, usize), f32>,
+    pub moves: BTreeMap<(usize, usize), f32>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[serde(serialize_with = "serialize_moves")]
+    #[serde(deserialize_with = "deserialize_moves")]
+    pub visits: BTreeMap<(usize, usize), u32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct QTable {
+    #[serde(serialize_with = "serialize_sorted")]
     qtable: HashMap<String, Moves>,
+    #[serde(default)]
+    symmetric: bool,
+}
+
+// Where a move led: to the position the opponent now faces, or to the end of the game with RLmax's reward.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome<'a> {
+    InPlay(&'a str),
+    Over(f32),
+}
+
+// One move of an episode, as the learners see it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transition<'a> {
+    pub key: &'a str,
+    pub mv: (usize, usize),
+    pub player: &'a str,
+    pub outcome: Outcome<'a>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateKey {
+    pub key: String,
+    pub symmetry: Symmetry,
+    pub spec: BoardSpec,
+}
+
+impl Transition<'_> {
+    // The reward once the game is over, otherwise the discounted value of the next position.
+    pub fn target(&self, gamma: f32, value: impl FnOnce(&str) -> f32) -> f32 {
+        match self.outcome {
+            Outcome::InPlay(next) => gamma * value(next),
+            Outcome::Over(reward) => reward,
+        }
+    }
 }
 
 impl Deref for Moves {
-    type Target = HashMap<(usize, usize), f32>;
+    type Target = BTreeMap<(usize, usize), f32>;
     fn deref(&self) -> &<Self as Deref>::Target {
         &self.moves
     }
@@ -51,39 +93,100 @@ impl DerefMut for QTable {
 }
 
 impl Moves {
-    pub fn new(m: Vec<(usize, usize)>) -> Moves {
-        let mut rng = thread_rng();
-        let moves: HashMap<(usize, usize), f32> = m
+    pub fn new(m: Vec<(usize, usize)>, rng: &mut GameRng) -> Moves {
+        let moves: BTreeMap<(usize, usize), f32> = m
             .into_iter()
             .map(|(x, y)| ((x, y), rng.gen_range(-0.15f32..0.15f32)))
             .collect();
-        Moves { moves }
+        Moves {
+            moves,
+            visits: BTreeMap::new(),
+        }
     }
-    pub fn select_max_move(&self) -> &(usize, usize) {
-        let mut rng = thread_rng();
+    pub fn select_max_move(&self, rng: &mut GameRng) -> &(usize, usize) {
         let max_moves = self
             .iter()
             .max_set_by(|((_, _), &value1), ((_, _), &value2)| value1.total_cmp(&value2))
             .into_iter()
             .map(|(key, _)| key)
             .collect::<Vec<&(usize, usize)>>();
-        *max_moves.choose(&mut rng).unwrap()
+        max_moves.choose(rng).unwrap()
     }
-    pub fn select_min_move(&self) -> &(usize, usize) {
-        let mut rng = thread_rng();
+    pub fn select_min_move(&self, rng: &mut GameRng) -> &(usize, usize) {
         let max_moves = self
             .iter()
             .min_set_by(|((_, _), &value1), ((_, _), &value2)| value1.total_cmp(&value2))
             .into_iter()
             .map(|(key, _)| key)
             .collect::<Vec<&(usize, usize)>>();
-        *max_moves.choose(&mut rng).unwrap()
+        max_moves.choose(rng).unwrap()
+    }
+    // Boltzmann distribution over the moves; the minimizing player favours low values instead.
+    pub fn softmax(&self, temperature: f32, minimize: bool) -> Vec<((usize, usize), f32)> {
+        let sign = if minimize { -1.0 } else { 1.0 };
+        let temperature = temperature.max(1e-6);
+        // Shifting by the best value keeps exp() from overflowing at low temperatures.
+        let best = self.values().map(|value| sign * value).fold(f32::NEG_INFINITY, f32::max);
+        let weights = self
+            .iter()
+            .map(|(mv, value)| (*mv, ((sign * value - best) / temperature).exp()))
+            .collect::<Vec<_>>();
+        let sum: f32 = weights.iter().map(|(_, weight)| weight).sum();
+        weights.into_iter().map(|(mv, weight)| (mv, weight / sum)).collect()
+    }
+    pub fn sample_softmax(&self, temperature: f32, minimize: bool, rng: &mut GameRng) -> ((usize, usize), f32) {
+        let policy = self.softmax(temperature, minimize);
+        let mv = policy.choose_weighted(rng, |(_, p)| *p).unwrap().0;
+        (mv, entropy(&policy))
+    }
+    pub fn visit(&mut self, mv: &(usize, usize)) {
+        *self.visits.entry(*mv).or_insert(0) += 1;
+    }
+    pub fn visits_of(&self, mv: &(usize, usize)) -> u32 {
+        self.visits.get(mv).copied().unwrap_or(0)
+    }
+    pub fn total_visits(&self) -> u32 {
+        self.visits.values().sum()
+    }
+    pub fn select_ucb_move(&self, c: f32, minimize: bool, rng: &mut GameRng) -> &(usize, usize) {
+        let sign = if minimize { -1.0 } else { 1.0 };
+        let log_total = (self.total_visits().max(1) as f32).ln();
+        let best_moves = self
+            .iter()
+            .map(|(mv, value)| {
+                let bonus = match self.visits_of(mv) {
+                    0 => f32::INFINITY,
+                    n => c * (log_total / n as f32).sqrt(),
+                };
+                (mv, sign * value + bonus)
+            })
+            .max_set_by(|a, b| a.1.total_cmp(&b.1))
+            .into_iter()
+            .map(|(mv, _)| mv)
+            .collect::<Vec<&(usize, usize)>>();
+        best_moves.choose(rng).unwrap()
+    }
+    pub fn select_least_visited_move(&self, rng: &mut GameRng) -> &(usize, usize) {
+        let least_visited = self
+            .keys()
+            .min_set_by_key(|mv| self.visits_of(mv))
+            .into_iter()
+            .collect::<Vec<&(usize, usize)>>();
+        least_visited.choose(rng).unwrap()
     }
 }
 
+// Shannon entropy in nats.
+pub fn entropy(policy: &[((usize, usize), f32)]) -> f32 {
+    -policy.iter().filter(|(_, p)| *p > 0.0).map(|(_, p)| p * p.ln()).sum::<f32>()
+}
+
 impl From<Vec<(usize, usize)>> for Moves {
     fn from(value: Vec<(usize, usize)>) -> Self {
-        let mut map: Moves = Moves {moves: HashMap::with_capacity(10)};
+        let mut map: Moves = Moves {
+            moves: BTreeMap::new(),
+            visits: BTreeMap::new(),
+        };
         for m in value {
             map.insert(m, -100.0);
         }
@@ -91,60 +194,175 @@ impl From<Vec<(usize, usize)>> for Moves {
     }
 }
 
+impl StateKey {
+    pub fn to_table(&self, mv: &(usize, usize)) -> (usize, usize) {
+        self.symmetry.apply(mv, &self.spec)
+    }
+    pub fn from_table(&self, mv: &(usize, usize)) -> (usize, usize) {
+        self.symmetry.inverse().apply(mv, &self.spec)
+    }
+}
+
+impl Default for QTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl QTable {
     pub fn new() -> Self {
         QTable {
             qtable: HashMap::with_capacity(11000),
+            symmetric: false,
+        }
+    }
+    pub fn with_symmetry() -> Self {
+        QTable {
+            qtable: HashMap::with_capacity(1500),
+            symmetric: true,
+        }
+    }
+    pub fn is_symmetric(&self) -> bool {
+        self.symmetric
+    }
+    pub fn state_key(&self, state: &GameState, player: &str) -> StateKey {
+        let (key, symmetry) = if self.symmetric {
+            state.canonicalize()
+        } else {
+            (state.to_state_key(), Symmetry::Identity)
+        };
+        StateKey {
+            key: key + player,
+            symmetry,
+            spec: state.spec,
         }
     }
-    pub fn max_move(&self, state_key: String) -> &(usize, usize) {
-        self.get(&state_key).unwrap().select_max_move()
+    // Keys the position on `next_state`, read off the board's bitboard when the table does not fold symmetries.
+    pub fn board_key(&self, board: &Board, player: &str) -> StateKey {
+        match board.bits.as_ref().filter(|_| !self.symmetric) {
+            Some(bits) => StateKey {
+                key: bits.to_state_key() + player,
+                symmetry: Symmetry::Identity,
+                spec: bits.spec,
+            },
+            None => self.state_key(&board.next_state, player),
+        }
+    }
+    pub fn entry_for(&mut self, key: &StateKey, state: &GameState, rng: &mut GameRng) -> &mut Moves {
+        self.entry_with(key, state.available_moves(), rng)
+    }
+    pub fn entry_with(
+        &mut self,
+        key: &StateKey,
+        moves: impl IntoIterator<Item = (usize, usize)>,
+        rng: &mut GameRng,
+    ) -> &mut Moves {
+        self.entry(key.key.clone())
+            .or_insert_with(|| Moves::new(moves.into_iter().map(|mv| key.to_table(&mv)).collect(), rng))
+    }
+    // New entries start at `value`, so untried moves look better than anything learned so far.
+    pub fn optimistic_entry_for(&mut self, key: &StateKey, state: &GameState, value: f32) -> &mut Moves {
+        self.optimistic_entry_with(key, state.available_moves(), value)
     }
-    pub fn min_move(&self, state_key: String) -> &(usize, usize) {
-        self.get(&state_key).unwrap().select_max_move()
+    pub fn optimistic_entry_with(
+        &mut self,
+        key: &StateKey,
+        moves: impl IntoIterator<Item = (usize, usize)>,
+        value: f32,
+    ) -> &mut Moves {
+        self.entry(key.key.clone()).or_insert_with(|| {
+            let mut moves = Moves::from(moves.into_iter().map(|mv| key.to_table(&mv)).collect::<Vec<_>>());
+            moves.values_mut().for_each(|v| *v = value);
+            moves
+        })
+    }
+    pub fn max_move(&self, state_key: String, rng: &mut GameRng) -> &(usize, usize) {
+        self.get(&state_key).unwrap().select_max_move(rng)
+    }
+    pub fn min_move(&self, state_key: String, rng: &mut GameRng) -> &(usize, usize) {
+        self.get(&state_key).unwrap().select_min_move(rng)
     }
     pub fn update_q_table(
         &mut self,
         current_state_key: &str,
-        next_state_key: &str,
+        next_state_key: Option<&str>,
         current_move: &(usize, usize),
         player: &str,
         reward: f32,
-        game_over: bool,
-    ) {
-        let expected = if game_over {
-            reward
-        } else if player == "RLmin" {
-            let max_value = self
-                .get(next_state_key)
-                .expect("The next state key should be present.")
-                .values()
-                .max_by(|&value1, &value2| value1.total_cmp(&value2))
-                .unwrap();
-            DISCOUNT_RATE.with_borrow(|drate| drate * max_value)
+        params: &Hyperparameters,
+    ) -> f32 {
+        let expected = match next_state_key {
+            None => reward,
+            Some(next_state_key) => params.gamma * self.greedy_value(next_state_key, player),
+        };
+        self.update_toward(current_state_key, current_move, expected, params)
+    }
+    // The value of the reply `player`'s opponent would pick greedily: RLmin minimizes, RLmax maximizes.
+    pub fn greedy_value(&self, next_state_key: &str, player: &str) -> f32 {
+        let next_moves = self
+            .get(next_state_key)
+            .expect("The next state key should be present.")
+            .values();
+        let next_value = if player == "RLmin" {
+            next_moves.max_by(|&value1, &value2| value1.total_cmp(value2))
         } else {
-            let min_value = self
-                .get(next_state_key)
-                .expect("The current key should be present.")
-                .values()
-                .min_by(|&value1, &value2| value1.total_cmp(&value2))
-                .unwrap();
-            DISCOUNT_RATE.with_borrow(|drate| drate * min_value)
+            next_moves.min_by(|&value1, &value2| value1.total_cmp(value2))
         };
-        self.get_mut(current_state_key)
+        *next_value.unwrap()
+    }
+    // The value of the reply under the opponent's epsilon-greedy policy.
+    pub fn expected_value(&self, next_state_key: &str, player: &str, epsilon: f32) -> f32 {
+        let next_moves = self.get(next_state_key).expect("The next state key should be present.");
+        let mean = next_moves.values().sum::<f32>() / next_moves.len() as f32;
+        (1.0 - epsilon) * self.greedy_value(next_state_key, player) + epsilon * mean
+    }
+    // Q-learning bootstraps from the reply the opponent would pick greedily.
+    pub fn q_learning(&mut self, t: &Transition, params: &Hyperparameters) -> f32 {
+        let target = t.target(params.gamma, |next| self.greedy_value(next, t.player));
+        self.update_toward(t.key, &t.mv, target, params)
+    }
+    // SARSA bootstraps from the reply that was actually played.
+    pub fn sarsa(&mut self, t: &Transition, reply: &(usize, usize), params: &Hyperparameters) -> f32 {
+        let target = t.target(params.gamma, |next| self.get(next).unwrap()[reply]);
+        self.update_toward(t.key, &t.mv, target, params)
+    }
+    // Expected SARSA bootstraps from the mean reply under the opponent's epsilon-greedy policy.
+    pub fn expected_sarsa(&mut self, t: &Transition, params: &Hyperparameters) -> f32 {
+        let target = t.target(params.gamma, |next| self.expected_value(next, t.player, params.epsilon));
+        self.update_toward(t.key, &t.mv, target, params)
+    }
+    pub fn update_toward(
+        &mut self,
+        current_state_key: &str,
+        current_move: &(usize, usize),
+        expected: f32,
+        params: &Hyperparameters,
+    ) -> f32 {
+        let value = self
+            .get_mut(current_state_key)
             .unwrap()
-            .entry(*current_move)
-            .and_modify(|value| LEARNING_RATE.with_borrow(|lrate| *value *= 1.0 - lrate))
-            .and_modify(|value| LEARNING_RATE.with_borrow(|lrate| *value += lrate * expected));
+            .get_mut(current_move)
+            .expect("The move should be present in the current state.");
+        let td_error = expected - *value;
+        *value += params.alpha * td_error;
+        td_error
     }
 }
 
-fn serialize_moves<S>(
-    moves: &HashMap<(usize, usize), f32>,
+fn serialize_sorted<S>(qtable: &HashMap<String, Moves>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(qtable.iter().sorted_by(|a, b| a.0.cmp(b.0)))
+}
+
+fn serialize_moves<S, V>(
+    moves: &BTreeMap<(usize, usize), V>,
     serializer: S,
 ) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
+    V: Serialize,
 {
     let mut map = serializer.serialize_map(Some(moves.len()))?;
     for (k, v) in moves {
@@ -154,22 +372,24 @@ where
     map.end()
 }
 
-fn deserialize_moves<'de, D>(deserializer: D) -> Result<HashMap<(usize, usize), f32>, D::Error>
+fn deserialize_moves<'de, D, V>(deserializer: D) -> Result<BTreeMap<(usize, usize), V>, D::Error>
 where
     D: Deserializer<'de>,
+    V: Deserialize<'de>,
 {
-    struct MapVisitor {
-        marker: PhantomData<fn() -> HashMap<(usize, usize), f32>>,
+    type MovesMap<V> = BTreeMap<(usize, usize), V>;
+    struct MapVisitor<V> {
+        marker: PhantomData<fn() -> MovesMap<V>>,
     }
-    impl MapVisitor {
+    impl<V> MapVisitor<V> {
         fn new() -> Self {
             MapVisitor {
                 marker: PhantomData,
             }
         }
     }
-    impl<'de> Visitor<'de> for MapVisitor {
-        type Value = HashMap<(usize, usize), f32>;
+    impl<'de, V: Deserialize<'de>> Visitor<'de> for MapVisitor<V> {
+        type Value = BTreeMap<(usize, usize), V>;
         fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
             formatter.write_str("my moves hashmap")
         }
@@ -177,14 +397,16 @@ where
         where
             M: MapAccess<'de>,
         {
-            let mut map = HashMap::with_capacity(access.size_hint().unwrap_or(0));
-            while let Some((key, value)) = access.next_entry::<String, f32>()? {
+            let mut map = BTreeMap::new();
+            while let Some((key, value)) = access.next_entry::<String, V>()? {
+                // Keys are written as "(r, c)"; rows and columns can run to several digits.
                 let k: (usize, usize) = key
-                    .chars()
-                    .filter(|r| r.is_digit(10))
-                    .map(|r| r.to_digit(10).unwrap() as usize)
+                    .trim_matches(|c| c == '(' || c == ')')
+                    .split(',')
+                    .map(|n| n.trim().parse::<usize>())
                     .collect_tuple()
-                    .expect("Expected two elements");
+                    .and_then(|(r, c)| r.ok().zip(c.ok()))
+                    .ok_or_else(|| de::Error::custom(format!("invalid move key {key:?}")))?;
                 map.insert(k, value);
             }
             Ok(map)
@@ -198,12 +420,12 @@ pub fn q_table_to_disk(path: &std::path::Path, q: &QTable) -> Result<(), anyhow:
     let today = dt.date_naive();
     let filename = "qtable-".to_owned() + (&today.to_string()) + r#".pickle"#;
     let filename_json = "qtable-".to_owned() + &today.to_string() + r#".json"#;
-    let q_json: PathBuf = [path, &Path::new(&filename_json)].iter().collect();
-    let q_pickle: PathBuf = [path, &Path::new(&filename)].iter().collect();
+    let q_json: PathBuf = [path, Path::new(&filename_json)].iter().collect();
+    let q_pickle: PathBuf = [path, Path::new(&filename)].iter().collect();
     let mut file = File::create(&q_pickle)?;
     let mut file_json = File::create(&q_json)?;
     let data_json = serde_json::to_string(&q).unwrap();
-    file_json.write_all(&data_json.as_bytes())?;
+    file_json.write_all(data_json.as_bytes())?;
     serde_pickle::to_writer(&mut file, q, serde_pickle::SerOptions::new())?;
     Ok(())
 }
@@ -229,10 +451,13 @@ pub fn q_table_from_disk_json(file: &std::path::Path) -> Result<QTable, anyhow::
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::board::Board;
+    use crate::rng;
 
     #[test]
     fn is_q_table_working() {
         let mut q = QTable::new();
+        let mut rng = rng::seeded(0);
         let mut test_board = Board::new();
         test_board.current_state.state[[1, 1]] = 'X';
         test_board.current_state.state[[2, 2]] = '0';
@@ -241,40 +466,178 @@ mod tests {
         println!("q_key is {}", q_key);
         let t = q
             .entry(q_key)
-            .or_insert(Moves::new(test_board.current_state.available_moves()));
+            .or_insert(Moves::new(test_board.current_state.available_moves(), &mut rng));
         println!("{t:?}");
         println!("{:?}", q.qtable);
         let q_key = test_board.current_state.to_state_key();
         test_board.current_state.state[[0, 0]] = 'X';
         let q_next_key = test_board.next_state.to_state_key();
-        let t_next = q
-            .entry(q_next_key)
-            .or_insert(Moves::new(test_board.current_state.available_moves()));
+        q.entry(q_next_key)
+            .or_insert(Moves::new(test_board.current_state.available_moves(), &mut rng));
         let q_next_key = test_board.next_state.to_state_key();
         println!("Before update, next state: {:?}", q.get(&q_next_key));
         println!("Before update: {:?}", q.get(&q_key));
-        q.update_q_table(&q_key, &q_next_key, &(0, 0), &"RLmax", 0.0, false);
+        let before = q.get(&q_key).unwrap()[&(0, 0)];
+        let td_error = q.update_q_table(&q_key, Some(&q_next_key), &(0, 0), "RLmax", 0.0, &Hyperparameters::default());
+        assert!((q.get(&q_key).unwrap()[&(0, 0)] - (before + 0.1 * td_error)).abs() < 1e-6);
         println!("After update: {:?}", q.get(&q_key));
-        let m = QTable::max_move(&q, q_key);
+        let m = QTable::max_move(&q, q_key, &mut rng);
         println!("{m:?}");
     }
     #[test]
     fn is_q_table_to_disk_working() {
         let mut q = QTable::new();
+        let mut rng = rng::seeded(0);
         let mut test_board = Board::new();
         test_board.current_state.state[[1, 1]] = 'X';
         test_board.current_state.state[[2, 2]] = '0';
         let q_key = test_board.current_state.to_state_key();
-        let t = q
-            .entry(q_key)
-            .or_insert(Moves::new(test_board.current_state.available_moves()));
+        q.entry(q_key)
+            .or_insert(Moves::new(test_board.current_state.available_moves(), &mut rng));
         let path = Path::new("../q_table_archive/");
-        let q_saved = q_table_to_disk(&path, &q);
+        let q_saved = q_table_to_disk(path, &q);
+        println!("Saving the QTable: {q_saved:?}");
+    }
+    #[test]
+    fn is_q_table_round_trip_working() {
+        let mut q = QTable::new();
+        let mut rng = rng::seeded(0);
+        let mut state = GameState::with_spec(BoardSpec::new(10, 10, 5));
+        state.state[[9, 9]] = 'X';
+        let key = state.to_state_key();
+        q.entry(key.clone()).or_insert(Moves::new(state.available_moves(), &mut rng));
+        let json = serde_json::to_string(&q).unwrap();
+        let loaded: QTable = serde_json::from_str(&json).unwrap();
+        let (saved, loaded) = (q.get(&key).unwrap(), loaded.get(&key).unwrap());
+        assert_eq!(loaded.len(), 99);
+        assert!(loaded.contains_key(&(9, 8)) && !loaded.contains_key(&(9, 9)));
+        assert!(saved.iter().all(|(mv, value)| loaded[mv] == *value));
+        assert!(serde_json::from_str::<QTable>(&json.replace("(9, 8)", "(9 8)")).is_err());
+    }
+    #[test]
+    fn is_symmetric_q_table_working() {
+        let mut q = QTable::with_symmetry();
+        let mut rng = rng::seeded(0);
+        let mut state = GameState::new();
+        state.state[[0, 0]] = 'X';
+        let key = q.state_key(&state, "RLmin");
+        q.entry_for(&key, &state, &mut rng);
+        *q.get_mut(&key.key).unwrap().get_mut(&key.to_table(&(0, 1))).unwrap() = 1.0;
+        let rotated = Symmetry::Rotate90.transform_state(&state);
+        let rotated_key = q.state_key(&rotated, "RLmin");
+        assert_eq!(rotated_key.key, key.key);
+        q.entry_for(&rotated_key, &rotated, &mut rng);
+        assert_eq!(q.len(), 1);
+        let best = rotated_key.from_table(q.get(&rotated_key.key).unwrap().select_max_move(&mut rng));
+        assert_eq!(best, (1, 2));
+    }
+    #[test]
+    fn is_expected_value_working() {
+        let mut q = QTable::new();
+        let mut moves = Moves::from(vec![(0, 0), (0, 1), (0, 2)]);
+        moves.insert((0, 0), 1.0);
+        moves.insert((0, 1), -1.0);
+        moves.insert((0, 2), 0.0);
+        q.insert("next".to_string(), moves);
+        assert_eq!(q.greedy_value("next", "RLmax"), -1.0);
+        assert_eq!(q.greedy_value("next", "RLmin"), 1.0);
+        assert!((q.expected_value("next", "RLmax", 0.3) + 0.7).abs() < 1e-6);
+        assert_eq!(q.expected_value("next", "RLmin", 0.0), 1.0);
+    }
+    #[test]
+    fn is_sarsa_working() {
+        let table = || {
+            let mut q = QTable::new();
+            let mut moves = Moves::from(vec![(0, 0), (0, 1), (0, 2)]);
+            moves.insert((0, 0), 1.0);
+            moves.insert((0, 1), -1.0);
+            moves.insert((0, 2), 0.0);
+            q.insert("next".to_string(), moves);
+            let mut moves = Moves::from(vec![(1, 1)]);
+            moves.insert((1, 1), 0.5);
+            q.insert("current".to_string(), moves);
+            q
+        };
+        let params = Hyperparameters {
+            alpha: 0.5,
+            gamma: 0.9,
+            epsilon: 0.3,
+            ..Hyperparameters::default()
+        };
+        let t = Transition {
+            key: "current",
+            mv: (1, 1),
+            player: "RLmax",
+            outcome: Outcome::InPlay("next"),
+        };
+        // RLmin's greedy reply is worth -1.0, but it actually played (0, 2), worth 0.0.
+        let (greedy, sampled, mean) = (-1.0, 0.0, 0.0);
+        let mut q = table();
+        assert!((q.q_learning(&t, &params) - (0.9 * greedy - 0.5)).abs() < 1e-6);
+        assert!((q.get("current").unwrap()[&(1, 1)] - (0.5 + 0.5 * (0.9 * greedy - 0.5))).abs() < 1e-6);
+        let mut q = table();
+        assert!((q.sarsa(&t, &(0, 2), &params) - (0.9 * sampled - 0.5)).abs() < 1e-6);
+        assert!((q.get("current").unwrap()[&(1, 1)] - 0.25).abs() < 1e-6);
+        // Expected SARSA weighs the greedy reply by 1 - epsilon and the uniform mean by epsilon.
+        let mut q = table();
+        let expected = 0.9 * (0.7 * greedy + 0.3 * mean);
+        assert!((q.expected_sarsa(&t, &params) - (expected - 0.5)).abs() < 1e-6);
+        assert!((q.get("current").unwrap()[&(1, 1)] - (0.5 + 0.5 * (expected - 0.5))).abs() < 1e-6);
+        // Once the game is over every learner moves toward the reward alone.
+        let over = Transition {
+            outcome: Outcome::Over(1.0),
+            ..t
+        };
+        let mut q = table();
+        assert!((q.sarsa(&over, &(0, 2), &params) - 0.5).abs() < 1e-6);
+        assert!((q.get("current").unwrap()[&(1, 1)] - 0.75).abs() < 1e-6);
     }
     #[test]
     fn is_moves_working() {
-        let moves: Vec<(usize, usize)> = vec![(0,2), (1,1), (3,3)];
+        let moves: Vec<(usize, usize)> = vec![(0, 2), (1, 1), (3, 3)];
         let moves_values: Moves = Moves::from(moves);
         println!("{:?}", moves_values);
     }
+    #[test]
+    fn is_visit_count_working() {
+        let mut moves = Moves::from(vec![(0, 0), (0, 1), (1, 1)]);
+        moves.insert((0, 0), 1.0);
+        moves.insert((0, 1), 0.0);
+        moves.insert((1, 1), 0.5);
+        let mut rng = rng::seeded(3);
+        moves.visit(&(0, 0));
+        moves.visit(&(0, 0));
+        moves.visit(&(1, 1));
+        assert_eq!((moves.visits_of(&(0, 0)), moves.total_visits()), (2, 3));
+        // Untried moves come first, whatever their value.
+        assert_eq!(moves.select_ucb_move(1.0, false, &mut rng), &(0, 1));
+        assert_eq!(moves.select_least_visited_move(&mut rng), &(0, 1));
+        moves.visit(&(0, 1));
+        assert_eq!(moves.select_ucb_move(0.0, false, &mut rng), &(0, 0));
+        assert_eq!(moves.select_ucb_move(0.0, true, &mut rng), &(0, 1));
+        // A large bonus favours the less tried move over the better one.
+        assert_eq!(moves.select_ucb_move(10.0, false, &mut rng), &(1, 1));
+
+        let json = serde_json::to_string(&moves).unwrap();
+        let decoded: Moves = serde_json::from_str(&json).unwrap();
+        assert_eq!((decoded.moves, decoded.visits), (moves.moves, moves.visits));
+    }
+    #[test]
+    fn is_softmax_working() {
+        let mut moves = Moves::from(vec![(0, 0), (0, 1)]);
+        moves.insert((0, 0), 1.0);
+        moves.insert((0, 1), 0.0);
+        let p = 1.0_f32.exp() / (1.0_f32.exp() + 1.0);
+        let policy = moves.softmax(1.0, false);
+        assert!((policy[0].1 - p).abs() < 1e-6);
+        assert!((moves.softmax(1.0, true)[1].1 - p).abs() < 1e-6);
+        // Hot policies are uniform, cold ones greedy.
+        assert!((entropy(&moves.softmax(1e6, false)) - 2.0_f32.ln()).abs() < 1e-4);
+        assert!(entropy(&moves.softmax(0.01, false)) < 1e-6);
+        let mut rng = rng::seeded(5);
+        let picks = (0..1_000)
+            .filter(|_| moves.sample_softmax(1.0, false, &mut rng).0 == (0, 0))
+            .count();
+        assert!((650..800).contains(&picks));
+    }
 }
@@ -0,0 +1,244 @@
+use crate::board::{Board, BoardSpec};
+use crate::config::{Hyperparameters, TrainingConfig};
+use crate::dqn::one_hot;
+use crate::env::TicTacToeEnv;
+use crate::metrics::{EpisodeStats, MetricsLog};
+use crate::mlp::Mlp;
+use crate::players::{Marks, Player};
+use crate::q_table::{self, QTable};
+use crate::rng::{self, GameRng};
+use ndarray::{Array1, Array2, ArrayView1, Axis};
+use rand::prelude::SliceRandom;
+use std::cell::Cell;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReinforceConfig {
+    pub hidden: usize,
+    pub policy_learning_rate: f32,
+    pub baseline_learning_rate: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReinforceAgent {
+    pub config: ReinforceConfig,
+    pub policy: Mlp,
+    // Estimates the return from a state; subtracting it leaves the gradient unbiased but less noisy.
+    pub baseline: Mlp,
+}
+
+// Samples its moves from the policy, so it keeps playing a mixed strategy after training.
+#[derive(Debug)]
+pub struct PolicyPlayer {
+    pub name: String,
+    pub mark: Marks,
+    pub policy: Mlp,
+    pub last_entropy: Cell<Option<f32>>,
+}
+
+impl Default for ReinforceConfig {
+    fn default() -> Self {
+        ReinforceConfig {
+            hidden: 64,
+            policy_learning_rate: 0.01,
+            baseline_learning_rate: 0.01,
+        }
+    }
+}
+
+// Softmax over the legal actions; illegal ones get no probability.
+pub fn masked_softmax(logits: ArrayView1<f32>, mask: &[bool]) -> Array1<f32> {
+    let best = logits
+        .iter()
+        .zip(mask)
+        .filter(|(_, &legal)| legal)
+        .fold(f32::NEG_INFINITY, |a, (&l, _)| a.max(l));
+    let mut p = Array1::zeros(logits.len());
+    for (i, &l) in logits.iter().enumerate() {
+        if mask[i] {
+            p[i] = (l - best).exp();
+        }
+    }
+    let sum = p.sum();
+    p / sum
+}
+
+// Samples an action from the policy and returns it with the policy's entropy.
+fn sample(policy: &Mlp, input: &Array1<f32>, mask: &[bool], cols: usize, rng: &mut GameRng) -> (usize, f32) {
+    let p = masked_softmax(policy.forward(input).view(), mask);
+    let legal: Vec<((usize, usize), f32)> = (0..mask.len())
+        .filter(|&i| mask[i])
+        .map(|i| ((i / cols, i % cols), p[i]))
+        .collect();
+    let ((r, c), _) = *legal.choose_weighted(rng, |(_, p)| *p).unwrap();
+    (r * cols + c, q_table::entropy(&legal))
+}
+
+impl ReinforceAgent {
+    pub fn new(spec: BoardSpec, config: ReinforceConfig, rng: &mut GameRng) -> Self {
+        let cells = spec.cells();
+        ReinforceAgent {
+            config,
+            policy: Mlp::new(&[3 * cells, config.hidden, cells], rng),
+            baseline: Mlp::new(&[3 * cells, config.hidden, 1], rng),
+        }
+    }
+    // One game against the environment's opponent, then a Monte Carlo policy-gradient step on all of its moves.
+    pub fn learn_episode(&mut self, env: &mut TicTacToeEnv, params: &Hyperparameters, rng: &mut GameRng) -> EpisodeStats {
+        let mut stats = EpisodeStats::default();
+        let mut obs = env.reset(None);
+        let mut steps = Vec::new();
+        loop {
+            let input = one_hot(&obs.state, obs.agent_mark);
+            let mask = env.legal_action_mask();
+            let (action, entropy) = sample(&self.policy, &input, &mask, env.spec.cols, rng);
+            stats.entropy += entropy;
+            stats.sampled_moves += 1;
+            let (next, reward, done, info) = env.step(action);
+            steps.push((input, mask, action, reward));
+            if done {
+                stats.winner = info.winner;
+                break;
+            }
+            obs = next;
+        }
+
+        let n = steps.len() as f32;
+        let mut inputs = Array2::zeros((steps.len(), steps[0].0.len()));
+        let mut returns = Array1::zeros(steps.len());
+        let mut g = 0.0;
+        for (row, (input, _, _, reward)) in steps.iter().enumerate().rev() {
+            g = reward + params.gamma * g;
+            returns[row] = g;
+            inputs.row_mut(row).assign(input);
+        }
+        let values = self.baseline.activations(&inputs);
+        let advantages = &returns - &values.last().unwrap().column(0);
+        let policy = self.policy.activations(&inputs);
+        // Gradient ascent on A * ln pi(a|s), written as descent on its negative.
+        let mut policy_delta = Array2::zeros(policy.last().unwrap().raw_dim());
+        for (row, (_, mask, action, _)) in steps.iter().enumerate() {
+            let p = masked_softmax(policy.last().unwrap().row(row), mask);
+            let mut delta = p * advantages[row] / n;
+            delta[*action] -= advantages[row] / n;
+            policy_delta.row_mut(row).assign(&delta);
+        }
+        self.policy.backward(&policy, policy_delta, self.config.policy_learning_rate);
+        let baseline_delta = advantages.mapv(|a| -a / n).insert_axis(Axis(1));
+        self.baseline.backward(&values, baseline_delta, self.config.baseline_learning_rate);
+
+        stats.advantage = advantages.mapv(f32::abs).sum();
+        stats.updates = steps.len();
+        stats
+    }
+    pub fn player(&self, name: String) -> PolicyPlayer {
+        PolicyPlayer {
+            name,
+            mark: Marks::None,
+            policy: self.policy.clone(),
+            last_entropy: Cell::new(None),
+        }
+    }
+}
+
+pub fn train(env: &mut TicTacToeEnv, config: &TrainingConfig, reinforce: ReinforceConfig) -> ReinforceAgent {
+    let mut rng = config.seed.map_or_else(rng::from_entropy, rng::seeded);
+    if let Some(seed) = config.seed {
+        env.reset(Some(seed));
+    }
+    let mut agent = ReinforceAgent::new(env.spec, reinforce, &mut rng);
+    let mut metrics = MetricsLog::open(config, false);
+    for episode in 0..config.num_episodes {
+        let params = config.at(episode);
+        let stats = agent.learn_episode(env, &params, &mut rng);
+        // A policy keeps no table of values.
+        metrics.record(episode + 1, &stats, 0, &params);
+    }
+    agent
+}
+
+impl Player for PolicyPlayer {
+    fn set_mark(&mut self, mark: Marks) {
+        self.mark = mark;
+    }
+    fn get_mark(&self) -> &Marks {
+        &self.mark
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn choose_move(&self, board: &Board, _q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        let state = &board.current_state;
+        let mask: Vec<bool> = state.iter().map(|&c| c == '-').collect();
+        let cols = state.spec.cols;
+        let (action, entropy) = sample(&self.policy, &one_hot(state, self.mark), &mask, cols, rng);
+        self.last_entropy.set(Some(entropy));
+        (action / cols, action % cols)
+    }
+    fn choose_move_k(&self, board: &Board, q: &mut QTable, rng: &mut GameRng) -> (usize, usize) {
+        self.choose_move(board, q, rng)
+    }
+    fn make_move(&self, board: &mut Board, mv: &(usize, usize)) {
+        let (a, b) = *mv;
+        *board.next_state.get_mut([a, b]).unwrap() = self.mark.as_char();
+    }
+    fn last_entropy(&self) -> Option<f32> {
+        self.last_entropy.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::RandomOpponent;
+    use crate::evaluate::{self, EvalOptions};
+    use crate::players::RandomPlayer;
+
+    #[test]
+    fn is_reinforce_working() {
+        assert_eq!(
+            masked_softmax(ArrayView1::from(&[0.0, 5.0, 0.0]), &[true, false, true]).to_vec(),
+            vec![0.5, 0.0, 0.5]
+        );
+
+        let spec = BoardSpec::default();
+        let mut env = TicTacToeEnv::new(spec, Box::new(RandomOpponent));
+        let path = std::env::temp_dir().join("learn_game_reinforce_metrics_test.csv");
+        let config = TrainingConfig {
+            num_episodes: 3_000,
+            seed: Some(3),
+            metrics_every: Some(1_000),
+            metrics_path: path.clone(),
+            ..TrainingConfig::default()
+        };
+        let mut agent = train(&mut env, &config, ReinforceConfig::default());
+        let player = agent.player("Reinforce".to_string());
+        let stats = agent.learn_episode(&mut env, &config.at(config.num_episodes), &mut rng::seeded(0));
+        assert!(stats.advantage > 0.0 && stats.td_error == 0.0);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let header: Vec<&str> = text.lines().next().unwrap().split(',').collect();
+        let last: Vec<&str> = text.lines().nth(3).unwrap().split(',').collect();
+        let column = |name| last[header.iter().position(|&h| h == name).unwrap()].parse::<f32>().unwrap();
+        assert_eq!(column("episode"), 3_000.0);
+        assert!(column("mean_advantage") > 0.0 && column("mean_entropy") > 0.0);
+        // The policy stays stochastic: every opening move keeps some probability.
+        let mut rng = rng::seeded(0);
+        player.choose_move(&Board::new(), &mut QTable::new(), &mut rng);
+        assert!(player.last_entropy().unwrap() > 0.0);
+
+        let random = RandomPlayer::new("Random".to_string());
+        let options = EvalOptions {
+            spec,
+            seed: Some(1),
+            ..EvalOptions::default()
+        };
+        let report = evaluate::evaluate(
+            Box::new(player),
+            &mut QTable::new(),
+            Box::new(random),
+            &mut QTable::new(),
+            200,
+            &options,
+        );
+        assert!(report.win_rate.estimate > 0.6, "{report}");
+    }
+}
@@ -0,0 +1,12 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+pub type GameRng = ChaCha8Rng;
+
+pub fn seeded(seed: u64) -> GameRng {
+    GameRng::seed_from_u64(seed)
+}
+
+pub fn from_entropy() -> GameRng {
+    GameRng::from_entropy()
+}
@@ -0,0 +1,391 @@
+use crate::bitboard::{BitBoard, WinMasks};
+use crate::board::{BoardSpec, GameState};
+use crate::players::Marks;
+use crate::q_table::Moves;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+pub const WIN: i32 = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TtEntry {
+    pub value: i32,
+    pub depth_left: usize,
+    pub bound: Bound,
+    pub best: Option<(usize, usize)>,
+}
+
+pub type TranspositionTable = HashMap<(u128, u128, Marks), TtEntry>;
+
+// What the searches need from a board; boards too large for a bitboard search the char array.
+pub trait Position {
+    fn spec(&self) -> BoardSpec;
+    fn play(&mut self, mv: &(usize, usize), mark: &Marks);
+    fn undo(&mut self, mv: &(usize, usize));
+    fn is_win(&self, mark: &Marks) -> bool;
+    // Only lines through the last move can have been completed by it.
+    fn is_win_through(&self, mark: &Marks, mv: &(usize, usize)) -> bool;
+    fn is_full(&self) -> bool;
+    fn stones(&self) -> usize;
+    fn moves(&self) -> Vec<(usize, usize)>;
+    fn nth_move(&self, n: usize) -> (usize, usize);
+    fn lines_through(&self, mv: &(usize, usize)) -> usize;
+    // Lines still open to `mark` and to nobody else.
+    fn open_lines(&self, mark: &Marks) -> i32;
+    // Only bitboards are keyed in the transposition table.
+    fn key(&self) -> Option<(u128, u128)>;
+}
+
+impl Position for BitBoard {
+    fn spec(&self) -> BoardSpec {
+        self.spec
+    }
+    fn play(&mut self, mv: &(usize, usize), mark: &Marks) {
+        BitBoard::play(self, mv, mark)
+    }
+    fn undo(&mut self, mv: &(usize, usize)) {
+        BitBoard::undo(self, mv)
+    }
+    fn is_win(&self, mark: &Marks) -> bool {
+        BitBoard::is_win(self, mark)
+    }
+    fn is_win_through(&self, mark: &Marks, mv: &(usize, usize)) -> bool {
+        BitBoard::is_win_through(self, mark, mv)
+    }
+    fn is_full(&self) -> bool {
+        BitBoard::is_full(self)
+    }
+    fn stones(&self) -> usize {
+        (self.crosses | self.noughts).count_ones() as usize
+    }
+    fn moves(&self) -> Vec<(usize, usize)> {
+        self.available_moves().collect()
+    }
+    fn nth_move(&self, n: usize) -> (usize, usize) {
+        self.available_moves().nth(n).unwrap()
+    }
+    fn lines_through(&self, mv: &(usize, usize)) -> usize {
+        WinMasks::for_spec(self.spec).by_cell[self.index(mv)].len()
+    }
+    fn open_lines(&self, mark: &Marks) -> i32 {
+        let (mine, theirs) = (self.marks(mark), self.marks(&mark.other()));
+        WinMasks::for_spec(self.spec)
+            .all
+            .iter()
+            .filter(|&&m| mine & m != 0 && theirs & m == 0)
+            .count() as i32
+    }
+    fn key(&self) -> Option<(u128, u128)> {
+        Some((self.crosses, self.noughts))
+    }
+}
+
+impl Position for GameState {
+    fn spec(&self) -> BoardSpec {
+        self.spec
+    }
+    fn play(&mut self, mv: &(usize, usize), mark: &Marks) {
+        self[[mv.0, mv.1]] = mark.as_char();
+    }
+    fn undo(&mut self, mv: &(usize, usize)) {
+        self[[mv.0, mv.1]] = '-';
+    }
+    fn is_win(&self, mark: &Marks) -> bool {
+        *self == mark.as_char()
+    }
+    fn is_win_through(&self, mark: &Marks, mv: &(usize, usize)) -> bool {
+        let (k, value) = (self.spec.win_len, mark.as_char());
+        let run = |dr: isize, dc: isize| {
+            (1..k as isize)
+                .take_while(|i| {
+                    let (r, c) = (mv.0 as isize + dr * i, mv.1 as isize + dc * i);
+                    r >= 0 && c >= 0 && self.get([r as usize, c as usize]) == Some(&value)
+                })
+                .count()
+        };
+        [(0, 1), (1, 0), (1, 1), (1, -1)]
+            .iter()
+            .any(|&(dr, dc)| 1 + run(dr, dc) + run(-dr, -dc) >= k)
+    }
+    fn is_full(&self) -> bool {
+        GameState::is_full(self)
+    }
+    fn stones(&self) -> usize {
+        self.iter().filter(|&&value| value != '-').count()
+    }
+    fn moves(&self) -> Vec<(usize, usize)> {
+        self.available_moves()
+    }
+    fn nth_move(&self, n: usize) -> (usize, usize) {
+        self.indexed_iter()
+            .filter(|(_, &value)| value == '-')
+            .nth(n)
+            .map(|(mv, _)| mv)
+            .unwrap()
+    }
+    fn lines_through(&self, mv: &(usize, usize)) -> usize {
+        let (k, rows, cols) = (self.spec.win_len as isize, self.spec.rows as isize, self.spec.cols as isize);
+        let (r, c) = (mv.0 as isize, mv.1 as isize);
+        let inside = |r: isize, c: isize| r >= 0 && r < rows && c >= 0 && c < cols;
+        [(0, 1), (1, 0), (1, 1), (1, -1)]
+            .iter()
+            .map(|(dr, dc)| {
+                (0..k)
+                    .filter(|i| {
+                        let (start_r, start_c) = (r - dr * i, c - dc * i);
+                        inside(start_r, start_c) && inside(start_r + dr * (k - 1), start_c + dc * (k - 1))
+                    })
+                    .count()
+            })
+            .sum()
+    }
+    fn open_lines(&self, mark: &Marks) -> i32 {
+        let (mine, theirs) = (mark.as_char(), mark.other().as_char());
+        lines_for(self.spec)
+            .iter()
+            .filter(|line| {
+                line.iter().any(|&(r, c)| self[[r, c]] == mine) && line.iter().all(|&(r, c)| self[[r, c]] != theirs)
+            })
+            .count() as i32
+    }
+    fn key(&self) -> Option<(u128, u128)> {
+        None
+    }
+}
+
+type Lines = Arc<Vec<Vec<(usize, usize)>>>;
+
+static LINES: OnceLock<Mutex<HashMap<BoardSpec, Lines>>> = OnceLock::new();
+
+// The char-array counterpart of `WinMasks::for_spec`, built once per spec.
+pub(crate) fn lines_for(spec: BoardSpec) -> Lines {
+    let mut cache = LINES
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .expect("The line cache should not be poisoned.");
+    Arc::clone(cache.entry(spec).or_insert_with(|| Arc::new(spec.lines())))
+}
+
+#[derive(Debug, Default)]
+pub struct AlphaBeta {
+    pub table: TranspositionTable,
+    pub max_depth: Option<usize>,
+    pub nodes: usize,
+}
+
+impl AlphaBeta {
+    pub fn new(max_depth: Option<usize>) -> Self {
+        AlphaBeta {
+            table: TranspositionTable::new(),
+            max_depth,
+            nodes: 0,
+        }
+    }
+
+    pub fn ordered_moves<P: Position>(position: &P, first: Option<(usize, usize)>) -> Vec<(usize, usize)> {
+        let mut moves = position.moves();
+        moves.sort_by_key(|mv| (Some(*mv) != first, Reverse(position.lines_through(mv))));
+        moves
+    }
+
+    pub fn heuristic<P: Position>(position: &P, max_mark: &Marks) -> i32 {
+        position.open_lines(max_mark) - position.open_lines(&max_mark.other())
+    }
+
+    fn depth_for<P: Position>(&self, position: &P) -> usize {
+        let empty = position.spec().cells() - position.stones();
+        self.max_depth.map_or(empty, |d| d.min(empty))
+    }
+
+    pub fn search<P: Position>(
+        &mut self,
+        position: &mut P,
+        to_move: &Marks,
+        max_mark: &Marks,
+        depth_left: usize,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        let last = to_move.other();
+        if position.is_win(&last) {
+            let value = WIN * (position.spec().cells() as i32 + 1 - position.stones() as i32);
+            return if last == *max_mark { value } else { -value };
+        }
+        if position.is_full() {
+            return 0;
+        }
+        if depth_left == 0 {
+            return Self::heuristic(position, max_mark);
+        }
+        let key = position.key().map(|(crosses, noughts)| (crosses, noughts, *max_mark));
+        let (alpha_start, beta_start) = (alpha, beta);
+        let mut first = None;
+        if let Some(entry) = key.and_then(|key| self.table.get(&key)) {
+            first = entry.best;
+            if entry.depth_left >= depth_left {
+                match entry.bound {
+                    Bound::Exact => return entry.value,
+                    Bound::Lower => alpha = alpha.max(entry.value),
+                    Bound::Upper => beta = beta.min(entry.value),
+                }
+                if alpha >= beta {
+                    return entry.value;
+                }
+            }
+        }
+        let is_max = to_move == max_mark;
+        let mut best_value = if is_max { i32::MIN } else { i32::MAX };
+        let mut best_move = None;
+        for mv in Self::ordered_moves(position, first) {
+            position.play(&mv, to_move);
+            let value = self.search(position, &to_move.other(), max_mark, depth_left - 1, alpha, beta);
+            position.undo(&mv);
+            if is_max {
+                if value > best_value {
+                    best_value = value;
+                    best_move = Some(mv);
+                }
+                alpha = alpha.max(value);
+            } else {
+                if value < best_value {
+                    best_value = value;
+                    best_move = Some(mv);
+                }
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        let bound = if best_value <= alpha_start {
+            Bound::Upper
+        } else if best_value >= beta_start {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        if let Some(key) = key {
+            self.table.insert(
+                key,
+                TtEntry {
+                    value: best_value,
+                    depth_left,
+                    bound,
+                    best: best_move,
+                },
+            );
+        }
+        best_value
+    }
+
+    pub fn evaluate_moves(&mut self, state: &GameState, mark: &Marks) -> Moves {
+        match state.to_bitboard() {
+            Some(mut bits) => self.evaluate_position(&mut bits, mark),
+            None => self.evaluate_position(&mut state.clone(), mark),
+        }
+    }
+
+    fn evaluate_position<P: Position>(&mut self, position: &mut P, mark: &Marks) -> Moves {
+        let mut moves = Moves::from(Vec::new());
+        for mv in Self::ordered_moves(position, None) {
+            position.play(&mv, mark);
+            let depth_left = self.depth_for(position);
+            let value = self.search(position, &mark.other(), mark, depth_left, i32::MIN, i32::MAX);
+            position.undo(&mv);
+            moves.insert(mv, value as f32);
+        }
+        moves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BoardSpec;
+    use crate::players::MinimaxPlayer;
+    use std::collections::BTreeSet;
+
+    fn best_set(moves: &Moves) -> BTreeSet<(usize, usize)> {
+        let best = moves.values().copied().fold(f32::MIN, f32::max);
+        moves.iter().filter(|(_, &v)| v == best).map(|(mv, _)| *mv).collect()
+    }
+
+    #[test]
+    fn is_alpha_beta_working() {
+        let mut state = GameState::new();
+        state[[0, 0]] = 'X';
+        state[[1, 1]] = '0';
+        state[[2, 2]] = 'X';
+        let mut search = AlphaBeta::new(None);
+        for _ in 0..2 {
+            let moves = search.evaluate_moves(&state, &Marks::NOUGHT);
+            let mut reference = Moves::from(state.available_moves());
+            for (mv, val) in reference.iter_mut() {
+                let mut child = state.clone();
+                child[[mv.0, mv.1]] = '0';
+                *val = MinimaxPlayer::minimax(child, &Marks::CROSS, &0, false) as f32;
+            }
+            assert_eq!(best_set(&moves), best_set(&reference));
+            assert_eq!(best_set(&moves), BTreeSet::from([(0, 1), (1, 0), (1, 2), (2, 1)]));
+        }
+        assert!(!search.table.is_empty());
+        let empty = search.evaluate_moves(&GameState::new(), &Marks::CROSS);
+        assert!(empty.values().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn is_move_ordering_working() {
+        let bits = GameState::new().to_bitboard().unwrap();
+        let moves = AlphaBeta::ordered_moves(&bits, None);
+        assert_eq!(moves[0], (1, 1));
+        assert!(moves[1..5].iter().all(|(r, c)| r % 2 == 0 && c % 2 == 0));
+        assert_eq!(AlphaBeta::ordered_moves(&bits, Some((0, 1)))[0], (0, 1));
+    }
+
+    #[test]
+    fn is_depth_limited_search_working() {
+        let mut state = GameState::with_spec(BoardSpec::square(5));
+        for c in 0..4 {
+            state[[2, c]] = 'X';
+        }
+        state[[0, 0]] = '0';
+        state[[0, 1]] = '0';
+        state[[4, 4]] = '0';
+        state[[4, 3]] = '0';
+        let mut search = AlphaBeta::new(Some(2));
+        let moves = search.evaluate_moves(&state, &Marks::CROSS);
+        assert_eq!(best_set(&moves), BTreeSet::from([(2, 4)]));
+    }
+
+    #[test]
+    fn is_large_board_search_working() {
+        // Too many squares for a bitboard, so the search runs on the char array.
+        let spec = BoardSpec::new(8, 17, 5);
+        let mut state = GameState::with_spec(spec);
+        for c in 0..4 {
+            state[[7, c]] = 'X';
+        }
+        for c in 10..13 {
+            state[[3, c]] = '0';
+        }
+        state[[0, 0]] = '0';
+        for small in [BoardSpec::square(3), BoardSpec::new(6, 7, 4)] {
+            let (chars, bits) = (GameState::with_spec(small), BitBoard::with_spec(small));
+            assert!(chars.moves().iter().all(|mv| chars.lines_through(mv) == bits.lines_through(mv)));
+        }
+        let mut search = AlphaBeta::new(Some(1));
+        let moves = search.evaluate_moves(&state, &Marks::CROSS);
+        assert_eq!(moves.len(), spec.cells() - 8);
+        assert_eq!(best_set(&moves), BTreeSet::from([(7, 4)]));
+        assert!(search.table.is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::bitboard::BitBoard;
+use crate::board::{BoardSpec, GameState};
+use crate::players::Marks;
+use crate::q_table::{Moves, QTable};
+use crate::rng::GameRng;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolvedPosition {
+    pub to_move: Marks,
+    pub outcome: Outcome,
+    pub distance: usize,
+    pub best_moves: Vec<(usize, usize)>,
+    pub move_values: Moves,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Solution {
+    pub spec: BoardSpec,
+    pub positions: HashMap<String, SolvedPosition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OracleScore {
+    pub positions: usize,
+    pub seen: usize,
+    pub optimal: usize,
+    pub accuracy: f32,
+}
+
+impl Outcome {
+    pub fn reverse(self) -> Self {
+        match self {
+            Outcome::Win => Outcome::Loss,
+            Outcome::Loss => Outcome::Win,
+            Outcome::Draw => Outcome::Draw,
+        }
+    }
+    fn rank(self) -> i32 {
+        match self {
+            Outcome::Win => 1,
+            Outcome::Draw => 0,
+            Outcome::Loss => -1,
+        }
+    }
+    pub fn score(self, distance: usize, spec: &BoardSpec) -> f32 {
+        (self.rank() * (spec.cells() as i32 + 1 - distance as i32)) as f32
+    }
+}
+
+impl Solution {
+    pub fn get(&self, state: &GameState) -> Option<&SolvedPosition> {
+        self.positions.get(&state.to_state_key())
+    }
+
+    pub fn to_q_table(&self) -> QTable {
+        let mut q = QTable::new();
+        for (key, position) in &self.positions {
+            if !position.best_moves.is_empty() {
+                q.insert(key.clone() + "max", position.move_values.clone());
+            }
+        }
+        q
+    }
+
+    pub fn score_q_table(&self, q: &QTable, player: &str, rng: &mut GameRng) -> OracleScore {
+        let mut score = OracleScore {
+            positions: 0,
+            seen: 0,
+            optimal: 0,
+            accuracy: 0.0,
+        };
+        for (key, position) in self.positions.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+            if position.best_moves.is_empty() {
+                continue;
+            }
+            score.positions += 1;
+            let state = state_from_key(key, self.spec);
+            let state_key = q.state_key(&state, player);
+            let Some(moves) = q.get(&state_key.key) else {
+                continue;
+            };
+            score.seen += 1;
+            let chosen = if player == "RLmin" {
+                moves.select_min_move(rng)
+            } else {
+                moves.select_max_move(rng)
+            };
+            if position.best_moves.contains(&state_key.from_table(chosen)) {
+                score.optimal += 1;
+            }
+        }
+        score.accuracy = score.optimal as f32 / score.positions.max(1) as f32;
+        score
+    }
+}
+
+pub fn state_from_key(key: &str, spec: BoardSpec) -> GameState {
+    let mut state = GameState::with_spec(spec);
+    for (square, value) in state.iter_mut().zip(key.chars()) {
+        *square = value;
+    }
+    state
+}
+
+pub fn solve(spec: BoardSpec) -> Solution {
+    let mut positions = HashMap::new();
+    let mut bits = BitBoard::with_spec(spec);
+    solve_position(&mut bits, &mut positions);
+    Solution { spec, positions }
+}
+
+fn solve_position(
+    bits: &mut BitBoard,
+    positions: &mut HashMap<String, SolvedPosition>,
+) -> (Outcome, usize) {
+    let key = bits.to_state_key();
+    if let Some(solved) = positions.get(&key) {
+        return (solved.outcome, solved.distance);
+    }
+    let to_move = bits.side_to_move();
+    let terminal = if bits.is_win(&to_move.other()) {
+        Some(Outcome::Loss)
+    } else if bits.is_full() {
+        Some(Outcome::Draw)
+    } else {
+        None
+    };
+    let mut move_values = Moves::from(Vec::new());
+    let mut results = Vec::new();
+    if terminal.is_none() {
+        for mv in bits.available_moves() {
+            bits.play(&mv, &to_move);
+            let (child, child_distance) = solve_position(bits, positions);
+            bits.undo(&mv);
+            let (outcome, distance) = (child.reverse(), child_distance + 1);
+            move_values.insert(mv, outcome.score(distance, &bits.spec));
+            results.push((mv, outcome, distance));
+        }
+    }
+    let (outcome, distance) = match terminal {
+        Some(outcome) => (outcome, 0),
+        None => results
+            .iter()
+            .map(|&(_, outcome, distance)| (outcome, distance))
+            .max_by_key(|&(outcome, distance)| match outcome {
+                Outcome::Win => (1, -(distance as i64)),
+                Outcome::Draw => (0, 0),
+                Outcome::Loss => (-1, distance as i64),
+            })
+            .unwrap(),
+    };
+    let best_moves = results
+        .iter()
+        .filter(|(_, o, _)| *o == outcome)
+        .map(|(mv, _, _)| *mv)
+        .collect();
+    positions.insert(
+        key,
+        SolvedPosition {
+            to_move,
+            outcome,
+            distance,
+            best_moves,
+            move_values,
+        },
+    );
+    (outcome, distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng;
+
+    #[test]
+    fn is_solver_working() {
+        let solution = solve(BoardSpec::default());
+        assert_eq!(solution.positions.len(), 5478);
+        let root = solution.get(&GameState::new()).unwrap();
+        assert_eq!(root.outcome, Outcome::Draw);
+        assert_eq!(root.distance, 9);
+        assert_eq!(root.best_moves.len(), 9);
+
+        let mut state = GameState::new();
+        state[[0, 0]] = 'X';
+        state[[1, 1]] = '0';
+        state[[0, 1]] = 'X';
+        let position = solution.get(&state).unwrap();
+        assert_eq!(position.to_move, Marks::NOUGHT);
+        assert_eq!(position.outcome, Outcome::Draw);
+        assert_eq!(position.best_moves, vec![(0, 2)]);
+        state[[2, 2]] = '0';
+        let position = solution.get(&state).unwrap();
+        assert_eq!((position.outcome, position.distance), (Outcome::Win, 1));
+
+        let q = solution.to_q_table();
+        assert_eq!(q.len(), 4520);
+        let mut rng = rng::seeded(0);
+        let score = solution.score_q_table(&q, "max", &mut rng);
+        assert_eq!((score.seen, score.optimal), (4520, 4520));
+        assert_eq!(solution.score_q_table(&QTable::new(), "RLmax", &mut rng).seen, 0);
+    }
+}
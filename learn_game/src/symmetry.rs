@@ -0,0 +1,142 @@
+use crate::board::{BoardSpec, GameState};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Symmetry {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipRows,
+    FlipCols,
+    Transpose,
+    AntiTranspose,
+}
+
+impl Symmetry {
+    pub fn all(spec: &BoardSpec) -> &'static [Symmetry] {
+        if spec.rows == spec.cols {
+            &[
+                Symmetry::Identity,
+                Symmetry::Rotate90,
+                Symmetry::Rotate180,
+                Symmetry::Rotate270,
+                Symmetry::FlipRows,
+                Symmetry::FlipCols,
+                Symmetry::Transpose,
+                Symmetry::AntiTranspose,
+            ]
+        } else {
+            &[
+                Symmetry::Identity,
+                Symmetry::Rotate180,
+                Symmetry::FlipRows,
+                Symmetry::FlipCols,
+            ]
+        }
+    }
+    pub fn inverse(self) -> Self {
+        match self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            other => other,
+        }
+    }
+    pub fn apply(self, mv: &(usize, usize), spec: &BoardSpec) -> (usize, usize) {
+        let (r, c) = *mv;
+        let (last_r, last_c) = (spec.rows - 1, spec.cols - 1);
+        match self {
+            Symmetry::Identity => (r, c),
+            Symmetry::Rotate90 => (c, last_r - r),
+            Symmetry::Rotate180 => (last_r - r, last_c - c),
+            Symmetry::Rotate270 => (last_c - c, r),
+            Symmetry::FlipRows => (last_r - r, c),
+            Symmetry::FlipCols => (r, last_c - c),
+            Symmetry::Transpose => (c, r),
+            Symmetry::AntiTranspose => (last_c - c, last_r - r),
+        }
+    }
+    pub fn transform_key(self, state: &GameState) -> String {
+        let spec = &state.spec;
+        let inverse = self.inverse();
+        let mut key = String::with_capacity(spec.cells());
+        for r in 0..spec.rows {
+            for c in 0..spec.cols {
+                let (a, b) = inverse.apply(&(r, c), spec);
+                key.push(state[[a, b]]);
+            }
+        }
+        key
+    }
+    pub fn transform_state(self, state: &GameState) -> GameState {
+        let mut transformed = GameState::with_spec(state.spec);
+        for (mv, value) in state.indexed_iter() {
+            let (a, b) = self.apply(&mv, &state.spec);
+            transformed[[a, b]] = *value;
+        }
+        transformed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::players::Marks;
+    use std::collections::HashSet;
+
+    fn reachable(state: &mut GameState, mark: Marks, seen: &mut HashSet<String>) {
+        if !seen.insert(state.to_state_key()) {
+            return;
+        }
+        if state.is_game_over(&mark.other()) != crate::board::IsGameOver::InPlay {
+            return;
+        }
+        for (a, b) in state.available_moves() {
+            state[[a, b]] = mark.as_char();
+            reachable(state, mark.other(), seen);
+            state[[a, b]] = '-';
+        }
+    }
+
+    #[test]
+    fn is_symmetry_working() {
+        let spec = BoardSpec::square(3);
+        for sym in Symmetry::all(&spec) {
+            let mv = sym.apply(&(0, 1), &spec);
+            assert_eq!(sym.inverse().apply(&mv, &spec), (0, 1));
+        }
+        let mut state = GameState::new();
+        state[[0, 1]] = 'X';
+        state[[2, 2]] = '0';
+        let (key, sym) = state.canonicalize();
+        assert_eq!(sym.transform_state(&state).to_state_key(), key);
+        let rotated = Symmetry::Rotate90.transform_state(&state);
+        assert_eq!(rotated.canonicalize().0, key);
+
+        let mut all = HashSet::new();
+        reachable(&mut GameState::new(), Marks::CROSS, &mut all);
+        assert_eq!(all.len(), 5478);
+        let canonical: HashSet<String> = all
+            .iter()
+            .map(|key| {
+                let mut state = GameState::new();
+                for (value, square) in key.chars().zip(state.iter_mut()) {
+                    *square = value;
+                }
+                state.canonicalize().0
+            })
+            .collect();
+        assert_eq!(canonical.len(), 765);
+    }
+
+    #[test]
+    fn is_rectangular_symmetry_working() {
+        let spec = BoardSpec::new(2, 3, 2);
+        assert_eq!(Symmetry::all(&spec).len(), 4);
+        let mut state = GameState::with_spec(spec);
+        state[[0, 0]] = 'X';
+        let mut mirrored = GameState::with_spec(spec);
+        mirrored[[1, 2]] = 'X';
+        assert_eq!(state.canonicalize().0, mirrored.canonicalize().0);
+    }
+}
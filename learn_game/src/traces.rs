@@ -0,0 +1,146 @@
+use crate::config::{Hyperparameters, TraceKind};
+use crate::q_table::{QTable, Transition};
+
+#[derive(Debug, Clone)]
+pub struct EligibilityTraces {
+    pub kind: TraceKind,
+    pub lambda: f32,
+    traces: Vec<(String, (usize, usize), f32)>,
+}
+
+impl EligibilityTraces {
+    pub fn new(kind: TraceKind, lambda: f32) -> Self {
+        EligibilityTraces {
+            kind,
+            lambda,
+            traces: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, key: &str, mv: &(usize, usize)) -> f32 {
+        self.traces
+            .iter()
+            .find(|(k, m, _)| k == key && m == mv)
+            .map_or(0.0, |(_, _, trace)| *trace)
+    }
+
+    pub fn visit(&mut self, key: &str, mv: &(usize, usize)) {
+        match self.traces.iter_mut().find(|(k, m, _)| k == key && m == mv) {
+            Some((_, _, trace)) => match self.kind {
+                TraceKind::Replacing => *trace = 1.0,
+                TraceKind::Accumulating => *trace += 1.0,
+            },
+            None => self.traces.push((key.to_owned(), *mv, 1.0)),
+        }
+    }
+
+    // Moves every traced pair towards its target by its share of the TD error, then decays the traces.
+    pub fn apply(&mut self, q: &mut QTable, td_error: f32, params: &Hyperparameters) {
+        for (key, mv, trace) in self.traces.iter_mut() {
+            let value = q.get_mut(key.as_str()).unwrap().get_mut(mv).unwrap();
+            *value += params.alpha * td_error * *trace;
+            *trace *= params.gamma * self.lambda;
+        }
+    }
+
+    // TD(lambda) sends the error of the greedy one-step target back along every traced pair.
+    pub fn learn(&mut self, q: &mut QTable, t: &Transition, params: &Hyperparameters) -> f32 {
+        let target = t.target(params.gamma, |next| q.greedy_value(next, t.player));
+        let td_error = target - q.get(t.key).unwrap()[&t.mv];
+        self.visit(t.key, &t.mv);
+        self.apply(q, td_error, params);
+        td_error
+    }
+
+    pub fn clear(&mut self) {
+        self.traces.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Algorithm, TrainingConfig};
+    use crate::q_table::{Moves, Outcome};
+    use crate::tests::seeded_game;
+
+    #[test]
+    fn is_eligibility_traces_working() {
+        let params = Hyperparameters {
+            alpha: 0.5,
+            gamma: 1.0,
+            ..Hyperparameters::default()
+        };
+        let mut q = QTable::new();
+        for key in ["first", "second"] {
+            let mut moves = Moves::from(vec![(0, 0)]);
+            moves.insert((0, 0), 0.0);
+            q.insert(key.to_string(), moves);
+        }
+        let mut traces = EligibilityTraces::new(TraceKind::Replacing, 0.5);
+        traces.visit("first", &(0, 0));
+        traces.apply(&mut q, 0.0, &params);
+        traces.visit("second", &(0, 0));
+        traces.apply(&mut q, 1.0, &params);
+        // The terminal reward reaches the first move, scaled by gamma * lambda.
+        assert_eq!(q.get("second").unwrap()[&(0, 0)], 0.5);
+        assert_eq!(q.get("first").unwrap()[&(0, 0)], 0.25);
+        assert_eq!(traces.get("first", &(0, 0)), 0.25);
+
+        traces.visit("first", &(0, 0));
+        assert_eq!(traces.get("first", &(0, 0)), 1.0);
+        let mut accumulating = EligibilityTraces::new(TraceKind::Accumulating, 0.5);
+        accumulating.visit("first", &(0, 0));
+        accumulating.apply(&mut q, 0.0, &params);
+        accumulating.visit("first", &(0, 0));
+        assert_eq!(accumulating.get("first", &(0, 0)), 1.5);
+        accumulating.clear();
+        assert_eq!(accumulating.get("first", &(0, 0)), 0.0);
+    }
+
+    #[test]
+    fn is_td_lambda_working() {
+        let params = Hyperparameters {
+            alpha: 0.5,
+            gamma: 0.9,
+            ..Hyperparameters::default()
+        };
+        let mut q = QTable::new();
+        for key in ["first", "second", "third"] {
+            let mut moves = Moves::from(vec![(0, 0)]);
+            moves.insert((0, 0), 0.0);
+            q.insert(key.to_string(), moves);
+        }
+        let mut traces = EligibilityTraces::new(TraceKind::Replacing, 0.8);
+        let step = |key, player, outcome| Transition {
+            key,
+            mv: (0, 0),
+            player,
+            outcome,
+        };
+        assert_eq!(traces.learn(&mut q, &step("first", "RLmax", Outcome::InPlay("second")), &params), 0.0);
+        assert_eq!(traces.learn(&mut q, &step("second", "RLmin", Outcome::InPlay("third")), &params), 0.0);
+        let td_error = traces.learn(&mut q, &step("third", "RLmax", Outcome::Over(1.0)), &params);
+        assert_eq!(td_error, 1.0);
+        // A pair k moves before the reward moves by alpha * td_error * (gamma * lambda)^k.
+        for (k, key) in ["third", "second", "first"].into_iter().enumerate() {
+            let expected = params.alpha * td_error * (params.gamma * traces.lambda).powi(k as i32);
+            assert!((q.get(key).unwrap()[&(0, 0)] - expected).abs() < 1e-6);
+        }
+
+        let train = |algorithm, lambda| {
+            let (mut game, mut q) = seeded_game(TrainingConfig {
+                seed: Some(8),
+                algorithm,
+                lambda,
+                ..TrainingConfig::default()
+            });
+            for _ in 0..300 {
+                game.learn_episode(Some(&mut q));
+            }
+            serde_json::to_string(&q).unwrap()
+        };
+        // With lambda = 0 only the last visited pair is eligible, which is plain one-step Q-learning.
+        assert_eq!(train(Algorithm::TdLambda, 0.0), train(Algorithm::QLearning, 0.0));
+    }
+}